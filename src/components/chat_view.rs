@@ -1,57 +1,159 @@
-use crate::models::{ActionStatus, ChatMessage};
+use crate::components::image_lightbox::ImageLightbox;
+use crate::models::{ActionStatus, AppConfig, ChatMessage};
+use crate::services::voice;
 use dioxus::{document::eval, prelude::*};
+use pulldown_cmark::{html as cmark_html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use std::sync::OnceLock;
+
+/// 合成并播放一段文本的语音
+///
+/// 复用同一个缓存（按文本哈希落盘），所以无论是用户点"🔊"手动朗读，还是
+/// `auto_speak` 自动朗读，重复的文本都不会重新请求 TTS 接口。
+/// Dioxus 桌面端的 webview 可以直接用本地文件路径当 `src`（跟 `ImageLightbox`
+/// 里图片的用法一致），所以这里播放时也直接把磁盘路径交给 `Audio`。
+async fn speak_text(profile: crate::models::ModelProfile, text: String) {
+    match voice::synthesize(&profile, &text).await {
+        Ok(path) => {
+            let path_literal =
+                serde_json::to_string(&path.to_string_lossy().to_string()).unwrap_or_default();
+            let _ = eval(&format!("new Audio({}).play();", path_literal));
+        }
+        Err(e) => println!("⚠️ 语音合成失败: {}", e),
+    }
+}
 
 #[derive(PartialEq)]
 enum TextSegment {
-    Text(String),
-    Code(String),
+    /// 已经过 ammonia 净化的富文本块 (标题/列表/引用/加粗斜体/行内代码等)
+    Html(String),
+    /// 围栏代码块，语言标签原样保留给 Highlight.js (e.g. `language-python`)
+    Code { lang: String, content: String },
 }
 
-// 🔥 新增：解析函数，将混合文本切分为 普通文本 和 代码块
-fn parse_markdown_segments(text: &str) -> Vec<TextSegment> {
-    let mut segments = Vec::new();
-    let mut parts = text.split("```");
+fn sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::default();
+        // 允许的最小标签集：段落/标题/列表/引用/加粗斜体/行内代码/表格
+        builder.add_tags(&["table", "thead", "tbody", "tr", "th", "td"]);
+        builder
+    })
+}
 
-    // 简单的偶数位置是文本，奇数位置是代码（假设代码块总是成对出现）
-    // 这是一个简化的解析，更健壮的方式是使用 pulldown-cmark 库
-    for (i, part) in parts.enumerate() {
-        if part.trim().is_empty() {
-            continue;
-        }
+/// 把 AI 回复中的 HTML 片段（如 `ChatMessage.table`）净化后再交给前端渲染，
+/// 剥离 `<script>`、事件处理属性、`javascript:` 链接等注入向量。
+pub fn sanitize_html(raw: &str) -> String {
+    sanitizer().clean(raw).to_string()
+}
 
-        if i % 2 == 0 {
-            segments.push(TextSegment::Text(part.to_string()));
-        } else {
-            // 去掉可能存在的 "python" 前缀
-            let code_content = if part.trim_start().starts_with("python") {
-                part.replacen("python", "", 1)
-            } else {
-                part.to_string()
-            };
-            segments.push(TextSegment::Code(code_content.trim().to_string()));
+/// 把一段 Markdown（如富文本输入框组合出的内容）整体转成净化后的 HTML
+///
+/// 跟 [`parse_markdown_segments`] 不同，这里不拆分围栏代码块——富文本输入框
+/// 组合出来的是一条完整的消息正文，不需要按代码块单独接 Highlight.js。
+pub fn markdown_to_html(raw: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = Parser::new_ext(raw, options);
+    let mut html = String::new();
+    cmark_html::push_html(&mut html, parser);
+    sanitize_html(html.trim())
+}
+
+/// 把一段 Markdown 还原成纯文本——去掉 `**`、`` ` ``、`- ` 等格式标记，只留下
+/// 文字本身。富文本输入框发给模型的就是这份纯文本，模型不需要关心格式标记。
+pub fn markdown_to_plain_text(raw: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = Parser::new_ext(raw, options);
+
+    let mut plain = String::new();
+    for event in parser {
+        match event {
+            Event::Text(t) | Event::Code(t) => plain.push_str(&t),
+            Event::SoftBreak | Event::HardBreak => plain.push('\n'),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item) => plain.push('\n'),
+            _ => {}
         }
     }
-    segments
+    plain.trim().to_string()
 }
 
-fn clean_text(text: &str) -> String {
-    let mut result = String::new();
+/// 基于 pulldown-cmark 的富文本解析
+///
+/// 之前手写的 `split("```")` 只能区分"文本"和"代码"两类，遇到表格、列表、
+/// 标题等一律当成纯文本糊在一起。这里改用真正的 CommonMark 解析器：
+/// 围栏代码块单独成段并保留语言标签给 Highlight.js；其余的块级内容
+/// （标题、列表、引用、加粗/斜体、行内代码）交给 pulldown-cmark 生成 HTML，
+/// 再过 ammonia 白名单净化后才允许进入 Dioxus 的 webview。
+fn parse_markdown_segments(text: &str) -> Vec<TextSegment> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+
+    let parser = Parser::new_ext(text, options);
+
+    let mut segments = Vec::new();
+    let mut html_buf: Vec<Event> = Vec::new();
     let mut in_code = false;
-    for line in text.lines() {
-        if line.trim().starts_with("```") {
-            in_code = !in_code;
-            continue;
+    let mut code_lang = String::new();
+    let mut code_buf = String::new();
+
+    let flush_html = |buf: &mut Vec<Event>, segments: &mut Vec<TextSegment>| {
+        if buf.is_empty() {
+            return;
+        }
+        let mut html = String::new();
+        cmark_html::push_html(&mut html, buf.drain(..));
+        let trimmed = html.trim();
+        if !trimmed.is_empty() {
+            segments.push(TextSegment::Html(sanitize_html(trimmed)));
         }
-        if !in_code {
-            result.push_str(line);
-            result.push('\n');
+    };
+
+    for event in parser {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                flush_html(&mut html_buf, &mut segments);
+                in_code = true;
+                code_buf.clear();
+                code_lang = match kind {
+                    CodeBlockKind::Fenced(lang) => lang.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_code = false;
+                segments.push(TextSegment::Code {
+                    lang: if code_lang.is_empty() {
+                        "python".to_string()
+                    } else {
+                        code_lang.clone()
+                    },
+                    content: code_buf.trim().to_string(),
+                });
+            }
+            Event::Text(t) if in_code => code_buf.push_str(&t),
+            other => html_buf.push(other),
         }
     }
-    result
-        .replace("下面是代码", "")
-        .replace("Here is the code", "")
-        .trim()
-        .to_string()
+    // 流式输出过程中，末尾的围栏可能还没闭合——把目前收到的内容当作
+    // "仍在继续输出"的代码块渲染，而不是丢弃。
+    if in_code {
+        segments.push(TextSegment::Code {
+            lang: if code_lang.is_empty() {
+                "python".to_string()
+            } else {
+                code_lang
+            },
+            content: code_buf.trim().to_string(),
+        });
+    } else {
+        flush_html(&mut html_buf, &mut segments);
+    }
+
+    segments
 }
 
 #[component]
@@ -61,15 +163,46 @@ pub fn ChatView(
     on_confirm: EventHandler<usize>,
     on_cancel: EventHandler<usize>,
     on_undo: EventHandler<usize>,
+    /// 用户点某条消息结果表下的"导出"按钮，带上该消息 id，调用方负责把它
+    /// 解析成一张 sheet 并写成 `.xlsx`
+    on_export: EventHandler<usize>,
+    /// 当前会话在磁盘上是否还有更早的历史消息未加载
+    #[props(default = false)]
+    has_more_history: bool,
+    /// 用户把 `#chat-scroll` 滚动到接近顶部时触发，调用方负责从磁盘取下一页并前置插入
+    #[props(default)]
+    on_load_more: EventHandler<()>,
+    /// 用于读取语音接口配置 (`active_profile`) 和 `auto_speak` 开关
+    config: Signal<AppConfig>,
 ) -> Element {
+    // 是否正是"向上翻页加载历史"触发的这次重渲染；是的话不跳到底部，
+    // 而是把视口锚定在翻页前最早那条消息上，这样新插入的旧消息不会造成页面跳动。
+    let mut is_loading_older = use_signal(|| false);
+    let mut top_anchor_id = use_signal(|| None::<usize>);
+    // 当前打开的 Lightbox：(该消息的全部图片, 起始下标)
+    let mut lightbox = use_signal(|| None::<(Vec<String>, usize)>);
+
     use_effect(move || {
         messages.read();
-        let _ = eval(
-            r#"setTimeout(() => {
-            const el = document.getElementById('chat-scroll');
-            if(el) el.scrollTop = el.scrollHeight;
-        }, 50);"#,
-        );
+
+        if is_loading_older() {
+            is_loading_older.set(false);
+            if let Some(anchor_id) = top_anchor_id() {
+                let _ = eval(&format!(
+                    r#"setTimeout(() => {{
+                        const el = document.getElementById('msg-{anchor_id}');
+                        if (el) el.scrollIntoView({{ block: 'start' }});
+                    }}, 30);"#,
+                ));
+            }
+        } else {
+            let _ = eval(
+                r#"setTimeout(() => {
+                const el = document.getElementById('chat-scroll');
+                if(el) el.scrollTop = el.scrollHeight;
+            }, 50);"#,
+            );
+        }
 
         // 触发 Highlight.js 对页面上所有代码块进行高亮
         let _ = eval(
@@ -78,11 +211,62 @@ pub fn ChatView(
                 if (window.hljs) {
                     window.hljs.highlightAll();
                 }
-            }, 100); 
+            }, 100);
         "#,
         );
     });
 
+    // 监听 #chat-scroll 的滚动位置，接近顶部时请求加载更早的历史。
+    // 只在组件挂载时绑定一次（effect 内不读取任何信号，因此不会随消息变化重复运行）。
+    use_effect(move || {
+        let mut scroll_probe = eval(
+            r#"
+            const el = document.getElementById('chat-scroll');
+            if (el && !el.dataset.scrollBound) {
+                el.dataset.scrollBound = '1';
+                el.addEventListener('scroll', () => {
+                    if (el.scrollTop < 60) {
+                        dioxus.send(true);
+                    }
+                });
+            }
+            "#,
+        );
+        spawn(async move {
+            while let Ok(true) = scroll_probe.recv::<bool>().await {
+                if has_more_history && !is_loading_older() {
+                    let first_id = messages.read().first().map(|m| m.id);
+                    top_anchor_id.set(first_id);
+                    is_loading_older.set(true);
+                    on_load_more.call(());
+                }
+            }
+        });
+    });
+
+    // 自动朗读：新到的一条 AI 回复"定型"（不再是加载中/流式输出）之后，
+    // 如果开关打开就自动念一遍，同一条消息只念一次。
+    let mut last_auto_spoken = use_signal(|| None::<usize>);
+    use_effect(move || {
+        let msgs = messages.read();
+        if !config.read().auto_speak {
+            return;
+        }
+        if let Some(last) = msgs.last() {
+            let settled = !matches!(last.status, ActionStatus::Loading | ActionStatus::Streaming);
+            if !last.is_user
+                && settled
+                && !last.text.trim().is_empty()
+                && last_auto_spoken() != Some(last.id)
+            {
+                last_auto_spoken.set(Some(last.id));
+                let profile = config.read().active_profile();
+                let text = last.text.clone();
+                spawn(speak_text(profile, text));
+            }
+        }
+    });
+
     let msgs = messages.read().clone();
 
     // 预渲染
@@ -91,29 +275,48 @@ pub fn ChatView(
         let has_code = msg.pending_code.is_some();
         let is_error = matches!(msg.status, ActionStatus::Error(_));
         let is_undone = matches!(msg.status, ActionStatus::Undone);
-        let display_text = clean_text(&msg.text);
         let bubble_class = if is_undone { "bubble undone-state" } else { "bubble" };
+        let undone_style = if is_undone {
+            "text-decoration: line-through; opacity: 0.7;"
+        } else {
+            ""
+        };
 
-        // 解析文本段落
-        let segments = parse_markdown_segments(&msg.text);
-
-        let content_elements = segments.into_iter().map(|seg| {
-            match seg {
-                TextSegment::Text(t) => rsx! {
-                    div { style: if is_undone { "white-space: pre-wrap; margin-bottom: 8px; text-decoration: line-through; opacity: 0.7;" } else { "white-space: pre-wrap; margin-bottom: 8px;" },
-                        "{t}"
-                    }
-                },
-                TextSegment::Code(c) => rsx! {
-                    // 🔥 渲染为 Highlight.js 可识别的结构
-                    div { style: "margin-bottom: 10px;",
-                        pre {
-                            code { class: "language-python", "{c}" }
-                        }
-                    }
+        // 富文本输入框组合出的消息自带净化后的 HTML，直接渲染，不再对 `text`
+        // 重新跑一遍 Markdown 解析（`text` 这时只是发给模型的纯文本版本）；
+        // 其余消息照旧：标题/列表/引用/行内格式由 pulldown-cmark 渲染为净化后的
+        // HTML，围栏代码块单独成段保留语言标签
+        let content_elements = if let Some(html) = &msg.rich_html {
+            vec![rsx! {
+                div {
+                    style: "margin-bottom: 8px; {undone_style}",
+                    dangerous_inner_html: "{html}",
                 }
-            }
-        });
+            }]
+        } else {
+            parse_markdown_segments(&msg.text)
+                .into_iter()
+                .map(|seg| match seg {
+                    TextSegment::Html(html) => rsx! {
+                        div {
+                            style: "margin-bottom: 8px; {undone_style}",
+                            dangerous_inner_html: "{html}",
+                        }
+                    },
+                    TextSegment::Code { lang, content } => rsx! {
+                        // 🔥 渲染为 Highlight.js 可识别的结构，语言标签来自围栏而非硬编码
+                        div { style: "margin-bottom: 10px;",
+                            pre {
+                                code { class: "language-{lang}", "{content}" }
+                            }
+                        }
+                    },
+                })
+                .collect()
+        };
+
+        let table_html = msg.table.as_ref().map(|t| sanitize_html(t));
+        let msg_images = msg.display_images();
 
         // 底部交互栏逻辑
         let bottom_actions = match msg.status {
@@ -173,13 +376,24 @@ pub fn ChatView(
         rsx! {
             div {
                 key: "{msg_id}",
+                id: "msg-{msg_id}",
                 class: if msg.is_user { "message msg-user" } else { "message msg-ai" },
 
                 div { class: "{bubble_class}",
-                    // 文本
-                    if !display_text.is_empty() {
-                        div { style: if is_undone { "white-space: pre-wrap; margin-bottom: 8px; text-decoration: line-through; opacity: 0.7;" } else { "white-space: pre-wrap; margin-bottom: 8px;" },
-                            "{display_text}"
+                    // 正文：标题/列表/引用/加粗斜体等块，围栏代码块已被拆分到独立的 content_elements 项中
+                    {content_elements}
+
+                    if let Some(table) = &table_html {
+                        div {
+                            class: "msg-table",
+                            style: "margin-top: 8px;",
+                            dangerous_inner_html: "{table}",
+                        }
+                        button {
+                            class: "export-btn",
+                            style: "margin-top: 6px; font-size: 12px;",
+                            onclick: move |_| on_export.call(msg_id),
+                            "📤 导出为 .xlsx"
                         }
                     }
 
@@ -216,11 +430,36 @@ pub fn ChatView(
                         }
                     }
 
-                    if let Some(img) = &msg.image {
-                        img {
-                            class: "msg-image",
-                            src: "{img}",
-                            style: "max-width: 100%; margin-top: 8px; border-radius: 4px;",
+                    if !msg_images.is_empty() {
+                        div { class: "msg-image-strip", style: "display: flex; gap: 6px; margin-top: 8px; flex-wrap: wrap;",
+                            for (i , img) in msg_images.iter().enumerate() {
+                                img {
+                                    key: "{i}",
+                                    class: "msg-image-thumb",
+                                    src: "{img}",
+                                    style: "max-width: 140px; max-height: 140px; border-radius: 4px; cursor: zoom-in; object-fit: cover;",
+                                    onclick: {
+                                        let thumbs = msg_images.clone();
+                                        move |_| lightbox.set(Some((thumbs.clone(), i)))
+                                    },
+                                }
+                            }
+                        }
+                    }
+
+                    if !msg.is_user && !msg.text.trim().is_empty() {
+                        button {
+                            class: "speak-btn",
+                            style: "margin-top: 6px; background: none; border: none; cursor: pointer; opacity: 0.6; font-size: 14px;",
+                            title: "朗读这条回复",
+                            onclick: {
+                                let text = msg.text.clone();
+                                move |_| {
+                                    let profile = config.read().active_profile();
+                                    spawn(speak_text(profile, text.clone()));
+                                }
+                            },
+                            "🔊"
                         }
                     }
 
@@ -231,6 +470,15 @@ pub fn ChatView(
     });
 
     rsx! {
-        div { id: "chat-scroll", class: "chat-scroll", {rendered_msgs} }
+        div { id: "chat-scroll", class: "chat-scroll",
+            if has_more_history {
+                div { class: "history-loading-indicator", "⬆ 上滑加载更早的记录…" }
+            }
+            {rendered_msgs}
+        }
+
+        if let Some((imgs, start)) = lightbox() {
+            ImageLightbox { images: imgs, start_index: start, on_close: move |_| lightbox.set(None) }
+        }
     }
 }
@@ -0,0 +1,78 @@
+use dioxus::prelude::*;
+
+/// 一条菜单项：文案 + 可选图标 + 选中后触发的回调
+///
+/// 回调只负责"做事"，菜单自己的收起（`on_dismiss`）由 [`ContextMenu`] 在点击
+/// 任意一项之后统一触发，调用方不需要在每个 `on_select` 里都记得关掉菜单。
+#[derive(Clone, PartialEq)]
+pub struct MenuItem {
+    pub label: String,
+    pub icon: Option<Asset>,
+    pub on_select: EventHandler<()>,
+}
+
+impl MenuItem {
+    pub fn new(label: impl Into<String>, on_select: EventHandler<()>) -> Self {
+        Self {
+            label: label.into(),
+            icon: None,
+            on_select,
+        }
+    }
+
+    pub fn with_icon(mut self, icon: Asset) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+}
+
+/// 通用右键/更多菜单：`DockCapsule` 的 `…` 按钮和整个胶囊的右键都复用这一个
+/// 组件，各自只负责拼 `items` 和给出锚点坐标——菜单本身不关心是谁打开的它。
+///
+/// 点击菜单外的任意位置或按 Escape 都会收起；背景层盖住整个窗口，所以"外部
+/// 点击"不需要额外的 DOM 坐标计算。
+#[component]
+pub fn ContextMenu(
+    /// 菜单左上角相对窗口的逻辑坐标，通常来自触发点击的 `MouseData::client_coordinates()`
+    x: f64,
+    y: f64,
+    items: Vec<MenuItem>,
+    on_dismiss: EventHandler<()>,
+) -> Element {
+    rsx! {
+        div {
+            class: "context-menu-backdrop",
+            style: "position: fixed; inset: 0; z-index: 999;",
+            tabindex: "-1",
+            onclick: move |_| on_dismiss.call(()),
+            oncontextmenu: move |evt| {
+                evt.prevent_default();
+                on_dismiss.call(());
+            },
+            onkeydown: move |evt| {
+                if evt.key() == Key::Escape {
+                    on_dismiss.call(());
+                }
+            },
+            div {
+                class: "context-menu",
+                style: "position: absolute; left: {x}px; top: {y}px; z-index: 1000;",
+                onclick: move |evt| evt.stop_propagation(),
+                for item in items.iter().cloned() {
+                    div {
+                        class: "context-menu-item",
+                        onclick: move |evt: Event<MouseData>| {
+                            evt.stop_propagation();
+                            item.on_select.call(());
+                            on_dismiss.call(());
+                        },
+                        if let Some(icon) = &item.icon {
+                            img { class: "context-menu-icon", src: "{icon}", draggable: false }
+                        }
+                        span { "{item.label}" }
+                    }
+                }
+            }
+        }
+    }
+}
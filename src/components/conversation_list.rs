@@ -0,0 +1,106 @@
+use crate::models::AppState;
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+/// 会话侧边栏：列出所有会话，支持新建 / 内联重命名 / 删除 / 切换激活项
+#[component]
+pub fn ConversationList(
+    state: Signal<AppState>,
+    on_new: EventHandler<()>,
+    on_select: EventHandler<Uuid>,
+    on_delete: EventHandler<Uuid>,
+) -> Element {
+    let mut editing_id = use_signal(|| None::<Uuid>);
+    let mut draft_title = use_signal(|| String::new());
+    let mut pending_delete = use_signal(|| None::<Uuid>);
+
+    let conversations = state.read().conversations.clone();
+    let active_id = state.read().active_id;
+
+    let items = conversations.into_iter().map(|conv| {
+        let conv_id = conv.id;
+        let is_active = active_id == Some(conv_id);
+        let is_editing = editing_id() == Some(conv_id);
+        let is_confirming_delete = pending_delete() == Some(conv_id);
+
+        rsx! {
+            div {
+                key: "{conv_id}",
+                class: if is_active { "conversation-item active" } else { "conversation-item" },
+                onclick: move |_| {
+                    if editing_id().is_none() {
+                        on_select.call(conv_id);
+                    }
+                },
+
+                if is_editing {
+                    input {
+                        class: "conversation-rename-input",
+                        value: "{draft_title}",
+                        oninput: move |evt| draft_title.set(evt.value()),
+                        onkeydown: move |evt| {
+                            if evt.key() == Key::Enter {
+                                let new_title = draft_title.read().clone();
+                                if !new_title.trim().is_empty() {
+                                    state.write().rename(conv_id, new_title);
+                                }
+                                editing_id.set(None);
+                            } else if evt.key() == Key::Escape {
+                                editing_id.set(None);
+                            }
+                        },
+                        onblur: move |_| editing_id.set(None),
+                    }
+                } else {
+                    div { class: "conversation-title", title: "{conv.title}", "{conv.title}" }
+                }
+
+                div { class: "conversation-actions",
+                    div {
+                        class: "conversation-action-btn",
+                        title: "重命名",
+                        onclick: move |evt| {
+                            evt.stop_propagation();
+                            draft_title.set(conv.title.clone());
+                            editing_id.set(Some(conv_id));
+                        },
+                        "✏️"
+                    }
+                    if is_confirming_delete {
+                        div {
+                            class: "conversation-action-btn danger",
+                            title: "确认删除",
+                            onclick: move |evt| {
+                                evt.stop_propagation();
+                                pending_delete.set(None);
+                                on_delete.call(conv_id);
+                            },
+                            "确认?"
+                        }
+                    } else {
+                        div {
+                            class: "conversation-action-btn",
+                            title: "删除会话",
+                            onclick: move |evt| {
+                                evt.stop_propagation();
+                                pending_delete.set(Some(conv_id));
+                            },
+                            "🗑️"
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    rsx! {
+        div { class: "conversation-list",
+            div {
+                class: "conversation-new-btn",
+                onclick: move |_| on_new.call(()),
+                "＋ 新建会话"
+            }
+            div { class: "conversation-scroll", {items} }
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use crate::models::{ChatMessage, WindowMode};
+use crate::components::context_menu::{ContextMenu, MenuItem};
+use crate::models::{AppConfig, ChatMessage, WindowMode};
+use crate::services::config::save_config;
 use dioxus::{
     core::{Element, Event, Task},
     desktop::{
@@ -64,24 +66,162 @@ fn atomic_update_bounds(window: &DesktopContext, x: f64, y: f64, w: f64, h: f64)
     window.set_inner_size(LogicalSize::new(w, h));
 }
 
+/// 按开关同步"预留停靠"状态：开了就登记 AppBar 并把 `rc` 定位到 `side`，关了
+/// 就登出。非 Windows 下什么都不做（继续走现有贴边行为）。
+fn sync_reserved_dock(window: &DesktopContext, side: DockSide, enabled: bool, bar_w_logical: f64) {
+    #[cfg(target_os = "windows")]
+    {
+        if enabled {
+            appbar::register(window);
+            appbar::set_position(window, side, bar_w_logical);
+        } else {
+            appbar::unregister(window);
+        }
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = (window, side, enabled, bar_w_logical);
+    }
+}
+
+/// "预留停靠"模式：把胶囊注册成 Windows 的 AppBar（跟任务栏是同一套机制），
+/// 这样桌面工作区会让出这条边的空间，最大化的窗口也不会再盖住胶囊。
+///
+/// 只在 Windows 下编译；非 Windows 平台没有这套机制，继续用现有的贴边行为
+/// 就够了（见 `atomic_update_bounds` 在非 Windows 下的 fallback 分支）。
+#[cfg(target_os = "windows")]
+mod appbar {
+    use super::{get_hwnd, DockSide};
+    use dioxus::desktop::DesktopContext;
+    use windows_sys::Win32::{
+        Foundation::{HWND, RECT},
+        UI::{
+            Shell::{
+                SHAppBarMessage, ABE_LEFT, ABE_RIGHT, ABM_NEW, ABM_QUERYPOS, ABM_REMOVE,
+                ABM_SETPOS, APPBARDATA,
+            },
+            WindowsAndMessaging::RegisterWindowMessageW,
+        },
+    };
+
+    fn wide_null(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    /// 私有回调消息 id：壳层状态变化（比如任务栏自己也在挪位置）时会往这个消息
+    /// 发通知，但接住它需要给 wry 的窗口挂一层 WndProc 子类化——这部分目前项目
+    /// 里没有现成的钩子，所以先只保证注册/登出和手动 `ABM_SETPOS` 这条主链路；
+    /// `ABN_POSCHANGED` 通知暂时没有被动响应，`dock_side` 变化时靠
+    /// `sync_reserved_dock` 重新调用下面的 `set_position` 来补上。
+    fn callback_message_id() -> u32 {
+        let name = wide_null("ExcelAgentReservedDockBarMsg");
+        unsafe { RegisterWindowMessageW(name.as_ptr()) }
+    }
+
+    fn edge_for(side: DockSide) -> u32 {
+        match side {
+            DockSide::Left => ABE_LEFT,
+            DockSide::Right => ABE_RIGHT,
+        }
+    }
+
+    fn rect_for(side: DockSide, bar_w: i32, screen_w: i32, screen_h: i32) -> RECT {
+        match side {
+            DockSide::Left => RECT {
+                left: 0,
+                top: 0,
+                right: bar_w,
+                bottom: screen_h,
+            },
+            DockSide::Right => RECT {
+                left: screen_w - bar_w,
+                top: 0,
+                right: screen_w,
+                bottom: screen_h,
+            },
+        }
+    }
+
+    fn new_data(hwnd: HWND) -> APPBARDATA {
+        let mut data: APPBARDATA = unsafe { std::mem::zeroed() };
+        data.cbSize = std::mem::size_of::<APPBARDATA>() as u32;
+        data.hWnd = hwnd;
+        data
+    }
+
+    /// 登记成 AppBar（发 `ABM_NEW`）；占住 `rc` 是 `set_position` 的事，所以"注册"
+    /// 和"定位"拆成两个函数。`sync_reserved_dock` 每次同步时两个都会调用。
+    pub fn register(window: &DesktopContext) -> Option<()> {
+        let hwnd = get_hwnd(window)?;
+        let mut data = new_data(hwnd);
+        data.uCallbackMessage = callback_message_id();
+        unsafe {
+            SHAppBarMessage(ABM_NEW, &mut data);
+        }
+        Some(())
+    }
+
+    /// 把预留区域定位到 `side`，宽度 `bar_w_logical`（逻辑像素，会按当前显示器
+    /// 缩放转成物理像素）；壳层可能会在 `ABM_QUERYPOS` 里调整 `rc`，调整后的
+    /// 结果再用 `ABM_SETPOS` 提交一遍，这样其它窗口避让的区域跟壳层算的一致。
+    pub fn set_position(window: &DesktopContext, side: DockSide, bar_w_logical: f64) -> Option<()> {
+        let hwnd = get_hwnd(window)?;
+        let monitor = window.current_monitor()?;
+        let scale = monitor.scale_factor();
+        let screen_w = monitor.size().width as i32;
+        let screen_h = monitor.size().height as i32;
+        let bar_w = (bar_w_logical * scale).round() as i32;
+
+        let mut data = new_data(hwnd);
+        data.uEdge = edge_for(side);
+        data.rc = rect_for(side, bar_w, screen_w, screen_h);
+        unsafe {
+            SHAppBarMessage(ABM_QUERYPOS, &mut data);
+            SHAppBarMessage(ABM_SETPOS, &mut data);
+        }
+        Some(())
+    }
+
+    /// 取消登记，把桌面工作区还回去——退出/取消预留停靠时必须调，不然这条边
+    /// 会一直被系统当成"被任务栏占着"。
+    pub fn unregister(window: &DesktopContext) -> Option<()> {
+        let hwnd = get_hwnd(window)?;
+        let mut data = new_data(hwnd);
+        unsafe {
+            SHAppBarMessage(ABM_REMOVE, &mut data);
+        }
+        Some(())
+    }
+}
+
 #[component]
 pub fn DockCapsule(
     mut window_mode: Signal<WindowMode>,
     mut messages: Signal<Vec<ChatMessage>>,
     mut last_file_path: Signal<String>,
+    config: Signal<AppConfig>,
 ) -> Element {
     let window = use_window();
     let mut dock_side = use_signal(|| DockSide::Right);
     let mut is_pinned = use_signal(|| false);
+    // 预留停靠：开着的时候胶囊注册成 Windows AppBar，桌面工作区/最大化窗口都
+    // 会让出这条边；非 Windows 下这个开关不生效，继续用现有贴边行为
+    let mut is_reserved_dock = use_signal(|| false);
     let mut is_hovering = use_signal(|| false);
     let mut is_file_hovering = use_signal(|| false);
     let mut drag_start_offset = use_signal(|| (0.0, 0.0));
     let mut is_dragging = use_signal(|| false);
     let mut debounce_task = use_signal(|| None::<Task>);
     let mut anim_ready = use_signal(|| false);
+    // "…" 按钮 / 整个胶囊的右键都开这同一个菜单，`menu_pos` 记的是触发点击那一下
+    // 的窗口内逻辑坐标，菜单就锚在那
+    let mut menu_open = use_signal(|| false);
+    let mut menu_pos = use_signal(|| (0.0_f64, 0.0_f64));
 
     const EXPANDED_W: f64 = 130.0;
     const EXPANDED_H: f64 = 160.0;
+    // 自动隐藏时仍露在屏幕内的那一小条，够用户看到胶囊还在、能把鼠标移回来就行
+    const PEEK_W: f64 = 16.0;
 
     let window_init = window.clone();
     use_effect(move || {
@@ -103,6 +243,55 @@ pub fn DockCapsule(
         });
     });
 
+    // 预留停靠开关或停靠边变化时都要重新同步一遍 AppBar 状态——换边本质上是
+    // "先让旧边的 rc"，所以这里直接按新的 `dock_side` 整个重新定位
+    let window_appbar = window.clone();
+    use_effect(move || {
+        sync_reserved_dock(&window_appbar, dock_side(), is_reserved_dock(), EXPANDED_W);
+    });
+
+    // 自动隐藏：悬停中/拖拽中/置顶/菜单开着/开关本身没开，都算"该显示"；否则滑到
+    // 只剩 `PEEK_W` 可见的位置。跟 `atomic_update_bounds` 走同一条物理像素路径，
+    // 高 DPI 下也不会跳像素；只挪 `x`，`y`/宽高不变，拖拽那条 loop 仍然是"瞬移跟手"，
+    // 这里只负责显隐两个目标位置之间的过渡动画，不冲突。
+    let window_auto_hide = window.clone();
+    use_effect(move || {
+        let should_show = is_hovering()
+            || is_dragging()
+            || is_pinned()
+            || menu_open()
+            || !config.read().auto_hide_dock;
+        let side = dock_side();
+        let window_async = window_auto_hide.clone();
+        spawn(async move {
+            let Some(monitor) = window_async.current_monitor() else {
+                return;
+            };
+            let scale = monitor.scale_factor();
+            let screen_w = monitor.size().width as f64 / scale;
+            let pos = window_async
+                .outer_position()
+                .unwrap_or(PhysicalPosition::new(0, 0));
+            let start_x = pos.x as f64 / scale;
+            let y = pos.y as f64 / scale;
+
+            let target_x = match (should_show, side) {
+                (true, DockSide::Left) => 0.0,
+                (true, DockSide::Right) => screen_w - EXPANDED_W,
+                (false, DockSide::Left) => PEEK_W - EXPANDED_W,
+                (false, DockSide::Right) => screen_w - PEEK_W,
+            };
+
+            const STEPS: i32 = 10;
+            for step in 1..=STEPS {
+                let t = step as f64 / STEPS as f64;
+                let x = start_x + (target_x - start_x) * t;
+                atomic_update_bounds(&window_async, x, y, EXPANDED_W, EXPANDED_H);
+                tokio::time::sleep(Duration::from_millis(12)).await;
+            }
+        });
+    });
+
     let window_drag_loop = window.clone();
     use_effect(move || {
         if is_dragging() {
@@ -237,6 +426,91 @@ pub fn DockCapsule(
         }
     };
 
+    // 跟拖拽结束时吸边的逻辑一样：量出当前 y，贴到另一侧屏幕边缘
+    let window_redock = window.clone();
+    let redock_to_other_side = move |_: ()| {
+        let window_async = window_redock.clone();
+        spawn(async move {
+            let Some(monitor) = window_async.current_monitor() else {
+                return;
+            };
+            let scale = monitor.scale_factor();
+            let screen_w = monitor.size().width as f64 / scale;
+            let pos = window_async
+                .outer_position()
+                .unwrap_or(PhysicalPosition::new(0, 0));
+            let y = pos.y as f64 / scale;
+            if dock_side() == DockSide::Left {
+                dock_side.set(DockSide::Right);
+                atomic_update_bounds(
+                    &window_async,
+                    screen_w - EXPANDED_W,
+                    y,
+                    EXPANDED_W,
+                    EXPANDED_H,
+                );
+            } else {
+                dock_side.set(DockSide::Left);
+                atomic_update_bounds(&window_async, 0.0, y, EXPANDED_W, EXPANDED_H);
+            }
+        });
+    };
+
+    let window_exit = window.clone();
+    let quit_app = move |_: ()| {
+        // 退出前把预留的 AppBar 空间还给桌面，不然这条边会一直被占着
+        if is_reserved_dock() {
+            sync_reserved_dock(&window_exit, dock_side(), false, EXPANDED_W);
+        }
+        window_exit.close();
+    };
+
+    let clear_conversation = move |_: ()| messages.write().clear();
+
+    let open_settings = move |_: ()| window_mode.set(WindowMode::Settings);
+
+    let toggle_pin = move |_: ()| is_pinned.set(!is_pinned());
+
+    let toggle_reserved_dock = move |_: ()| is_reserved_dock.set(!is_reserved_dock());
+
+    let toggle_auto_hide = move |_: ()| {
+        let mut cfg = config.read().clone();
+        cfg.auto_hide_dock = !cfg.auto_hide_dock;
+        config.set(cfg.clone());
+        save_config(&cfg);
+    };
+
+    let menu_items = vec![
+        MenuItem::new("切换停靠边", redock_to_other_side.into()),
+        MenuItem::new(
+            if is_pinned() {
+                "取消置顶"
+            } else {
+                "置顶"
+            },
+            toggle_pin.into(),
+        ),
+        MenuItem::new(
+            if is_reserved_dock() {
+                "取消预留停靠"
+            } else {
+                "预留停靠 (Windows)"
+            },
+            toggle_reserved_dock.into(),
+        ),
+        MenuItem::new(
+            if config.read().auto_hide_dock {
+                "取消自动隐藏"
+            } else {
+                "自动隐藏"
+            },
+            toggle_auto_hide.into(),
+        ),
+        MenuItem::new("打开配置", open_settings.into()),
+        MenuItem::new("清空会话", clear_conversation.into()),
+        MenuItem::new("退出", quit_app.into()),
+    ];
+
     let container_cls = format!(
         "dock-container {}",
         if dock_side() == DockSide::Left {
@@ -262,6 +536,16 @@ pub fn DockCapsule(
     };
 
     rsx! {
+        // 深色外观：颜色变量跟着 `main.rs` 写在 <html data-theme="..."> 上的属性走，
+        // 不再单独查 `prefers-color-scheme`——用户在设置里选的 Light/Dark 要能
+        // 覆盖系统外观，纯 CSS 媒体查询做不到这一点
+        document::Style { r#"
+            [data-theme="dark"] .main-capsule, [data-theme="dark"] .secondary-grid {{ background: #2a2a2a; color: #ddd; border-color: #444; }}
+            [data-theme="dark"] .context-menu {{ background: #2a2a2a; color: #ddd; border-color: #444; }}
+            [data-theme="dark"] .context-menu-item:hover {{ background: #3a3a3a; }}
+            [data-theme="dark"] .grid-btn.autohide.active {{ background: #2d4a66; }}
+        "# }
+
         div {
             class: "{container_cls}",
             style: "{visibility_style} {align_style}",
@@ -271,7 +555,12 @@ pub fn DockCapsule(
                 ondragover: handle_drag_over,
                 ondragleave: handle_drag_leave,
                 ondrop: handle_drop,
-                oncontextmenu: move |evt| evt.prevent_default(),
+                oncontextmenu: move |evt: Event<MouseData>| {
+                    evt.prevent_default();
+                    let coords = evt.client_coordinates();
+                    menu_pos.set((coords.x, coords.y));
+                    menu_open.set(true);
+                },
 
                 div {
                     class: "main-capsule",
@@ -330,9 +619,44 @@ pub fn DockCapsule(
                             draggable: false,
                         }
                     }
-                    div { class: "grid-btn more", "…" }
+                    div {
+                        class: if config.read().auto_hide_dock { "grid-btn autohide active" } else { "grid-btn autohide" },
+                        title: "自动隐藏",
+                        onclick: move |evt| {
+                            evt.stop_propagation();
+                            let mut cfg = config.read().clone();
+                            cfg.auto_hide_dock = !cfg.auto_hide_dock;
+                            config.set(cfg.clone());
+                            save_config(&cfg);
+                        },
+                        img {
+                            class: "menu-icon",
+                            src: if config.read().auto_hide_dock { asset!("assets/autohide_active.png") } else { asset!("assets/autohide.png") },
+                            draggable: false,
+                        }
+                    }
+                    div {
+                        class: "grid-btn more",
+                        title: "更多",
+                        onclick: move |evt: Event<MouseData>| {
+                            evt.stop_propagation();
+                            let coords = evt.client_coordinates();
+                            menu_pos.set((coords.x, coords.y));
+                            menu_open.set(true);
+                        },
+                        "…"
+                    }
                 }
             }
         }
+
+        if menu_open() {
+            ContextMenu {
+                x: menu_pos().0,
+                y: menu_pos().1,
+                items: menu_items,
+                on_dismiss: move |_| menu_open.set(false),
+            }
+        }
     }
 }
@@ -0,0 +1,20 @@
+use dioxus::desktop::use_global_shortcut;
+use dioxus::prelude::*;
+
+/// 两个全局热键（召唤/收回主窗口）的实际注册点
+///
+/// `use_global_shortcut` 本身不支持"运行时改绑定"，所以重新注册靠 `main.rs`
+/// 在 `summon`/`dismiss` 变化时给这个组件换一个新 `key`，把它整个重新挂载一遍——
+/// 比在这里手写"先反注册旧的、再注册新的"状态机要简单可靠。
+#[component]
+pub fn GlobalHotkeys(
+    summon: String,
+    dismiss: String,
+    on_summon: EventHandler<()>,
+    on_dismiss: EventHandler<()>,
+) -> Element {
+    let _ = use_global_shortcut(summon, move |_state| on_summon.call(()));
+    let _ = use_global_shortcut(dismiss, move |_state| on_dismiss.call(()));
+
+    rsx! {}
+}
@@ -0,0 +1,135 @@
+use dioxus::prelude::*;
+
+/// 点开 AI 生成的图表/截图时弹出的全屏预览
+///
+/// 支持 缩放到适应窗口 / 缩放到原始尺寸 切换、拖拽平移，以及把当前图片
+/// 另存为用户指定路径。当一条消息产出多张图（例如"思考过程"里连着跑出
+/// 好几张 matplotlib 图表），缩略图条喂给同一个 Lightbox，点击任意一张都
+/// 能在其中左右切换而不用重新打开。
+#[component]
+pub fn ImageLightbox(
+    images: Vec<String>,
+    start_index: usize,
+    on_close: EventHandler<()>,
+) -> Element {
+    let mut index = use_signal(|| start_index.min(images.len().saturating_sub(1)));
+    // true = 缩放到适应窗口 (contain)，false = 原始尺寸 (1:1，可滚动查看细节)
+    let mut zoom_to_fit = use_signal(|| true);
+    let mut is_panning = use_signal(|| false);
+    let mut pan_offset = use_signal(|| (0.0_f64, 0.0_f64));
+    let mut pan_start = use_signal(|| (0.0_f64, 0.0_f64));
+    let mut save_status = use_signal(|| None::<String>);
+
+    let current_path = images.get(index()).cloned().unwrap_or_default();
+
+    let img_style = if zoom_to_fit() {
+        "max-width: 100%; max-height: 100%; object-fit: contain; cursor: zoom-in;".to_string()
+    } else {
+        format!(
+            "max-width: none; cursor: grab; transform: translate({}px, {}px);",
+            pan_offset().0,
+            pan_offset().1
+        )
+    };
+
+    let save_as = move |_| {
+        let path = current_path.clone();
+        spawn(async move {
+            if let Some(dest) = rfd::AsyncFileDialog::new()
+                .set_file_name(
+                    std::path::Path::new(&path)
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_else(|| "chart.png".to_string()),
+                )
+                .save_file()
+                .await
+            {
+                match std::fs::copy(&path, dest.path()) {
+                    Ok(_) => save_status.set(Some("✅ 已保存".to_string())),
+                    Err(e) => save_status.set(Some(format!("❌ 保存失败: {}", e))),
+                }
+            }
+        });
+    };
+
+    rsx! {
+        div {
+            class: "lightbox-overlay",
+            style: "position: fixed; inset: 0; background: rgba(0,0,0,0.85); z-index: 1000; display: flex; align-items: center; justify-content: center;",
+            onclick: move |_| on_close.call(()),
+            onkeydown: move |evt| {
+                if evt.key() == Key::Escape {
+                    on_close.call(());
+                }
+            },
+
+            div {
+                class: "lightbox-content",
+                style: "position: relative; max-width: 90vw; max-height: 90vh; overflow: {if zoom_to_fit() { \"hidden\" } else { \"auto\" }};",
+                onclick: move |evt| evt.stop_propagation(),
+
+                img {
+                    src: "{current_path}",
+                    style: "{img_style}",
+                    draggable: false,
+                    onclick: move |_| zoom_to_fit.set(!zoom_to_fit()),
+                    onmousedown: move |evt| {
+                        if !zoom_to_fit() {
+                            let coords = evt.client_coordinates();
+                            pan_start.set((coords.x - pan_offset().0, coords.y - pan_offset().1));
+                            is_panning.set(true);
+                        }
+                    },
+                    onmousemove: move |evt| {
+                        if is_panning() {
+                            let coords = evt.client_coordinates();
+                            let start = pan_start();
+                            pan_offset.set((coords.x - start.0, coords.y - start.1));
+                        }
+                    },
+                    onmouseup: move |_| is_panning.set(false),
+                }
+
+                div {
+                    class: "lightbox-toolbar",
+                    style: "position: absolute; bottom: 10px; left: 50%; transform: translateX(-50%); display: flex; gap: 10px; background: rgba(0,0,0,0.6); padding: 6px 12px; border-radius: 20px;",
+
+                    if images.len() > 1 {
+                        button {
+                            onclick: move |_| {
+                                let len = images.len();
+                                index.set((index() + len - 1) % len);
+                                pan_offset.set((0.0, 0.0));
+                            },
+                            "◀"
+                        }
+                        div { style: "color: #fff; font-size: 12px;", "{index() + 1}/{images.len()}" }
+                        button {
+                            onclick: move |_| {
+                                let len = images.len();
+                                index.set((index() + 1) % len);
+                                pan_offset.set((0.0, 0.0));
+                            },
+                            "▶"
+                        }
+                    }
+
+                    button {
+                        onclick: move |_| zoom_to_fit.set(!zoom_to_fit()),
+                        if zoom_to_fit() { "🔍 原始尺寸" } else { "🔳 适应窗口" }
+                    }
+                    button { onclick: save_as, "💾 保存为..." }
+                    button { onclick: move |_| on_close.call(()), "✕ 关闭" }
+                }
+
+                if let Some(status) = save_status() {
+                    div {
+                        style: "position: absolute; top: 10px; right: 10px; background: rgba(0,0,0,0.7); color: #fff; padding: 4px 10px; border-radius: 6px; font-size: 12px;",
+                        "{status}"
+                    }
+                }
+            }
+        }
+    }
+}
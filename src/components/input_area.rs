@@ -1,35 +1,173 @@
-use crate::models::{AppConfig, ChatMessage, PyExecResult};
-use crate::services::{ai, python};
+use crate::components::chat_view::{markdown_to_html, markdown_to_plain_text};
+use crate::models::{AppConfig, CellRange, ChatMessage, ExportSheet};
+use crate::services::{ai, export, python, voice};
+use base64::{engine::general_purpose, Engine as _};
+use dioxus::document::{eval, Eval};
 use dioxus::prelude::*;
 use tokio::task;
 use uuid::Uuid;
 
+/// 浏览器端用 `MediaRecorder` 录音，点击停止前一直录，停止后把整段录音
+/// Base64 编码经 `dioxus.send` 传回来。走的是 eval 的双向通道：JS 先
+/// await dioxus.recv() 等 Rust 发来停止信号，再用 dioxus.send 把录音交回 Rust。
+const MIC_RECORDER_JS: &str = r#"
+const stream = await navigator.mediaDevices.getUserMedia({ audio: true });
+const recorder = new MediaRecorder(stream);
+const chunks = [];
+recorder.ondataavailable = (e) => { if (e.data.size > 0) chunks.push(e.data); };
+recorder.start();
+await dioxus.recv();
+const stopped = new Promise((resolve) => {
+    recorder.onstop = async () => {
+        const blob = new Blob(chunks, { type: "audio/webm" });
+        const buf = await blob.arrayBuffer();
+        const bytes = new Uint8Array(buf);
+        let binary = "";
+        for (let i = 0; i < bytes.byteLength; i++) binary += String.fromCharCode(bytes[i]);
+        resolve(btoa(binary));
+    };
+});
+recorder.stop();
+stream.getTracks().forEach((t) => t.stop());
+dioxus.send(await stopped);
+"#;
+
+/// 把从剪贴板粘贴来的表格片段（Excel/Sheets 复制出来的都是 Tab 分隔）转成一段
+/// Markdown 表格，插进富文本输入框里——跟 `SheetView` 里 Ctrl+V 解析剪贴板的
+/// 思路一样，按行/制表符拆分，第一行当表头
+fn tsv_to_markdown_table(text: &str) -> Option<String> {
+    let rows: Vec<Vec<&str>> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split('\t').collect())
+        .collect();
+    let header = rows.first()?;
+
+    let render_row = |cells: &[&str]| {
+        let mut line = String::from("|");
+        for cell in cells {
+            line.push(' ');
+            line.push_str(cell.trim());
+            line.push_str(" |");
+        }
+        line
+    };
+
+    let mut out = render_row(header);
+    out.push('\n');
+    out.push_str(&render_row(&vec!["---"; header.len()]));
+    for row in rows.iter().skip(1) {
+        out.push('\n');
+        out.push_str(&render_row(row));
+    }
+    Some(out)
+}
+
 #[component]
 pub fn InputArea(
     messages: Signal<Vec<ChatMessage>>,
     last_file_path: Signal<String>,
     is_loading: Signal<bool>,
     config: Signal<AppConfig>,
+    /// `SheetView` 里当前框选的单元格区域，随发送的消息一起带给 AI
+    selected_range: Signal<Option<CellRange>>,
 ) -> Element {
     let mut input_text = use_signal(|| String::new());
+    let mut is_recording = use_signal(|| false);
+    let mut recorder_handle: Signal<Option<Eval>> = use_signal(|| None);
+    // 富文本模式：输入框换成多行 textarea，配一排格式化按钮；Enter 换行、
+    // Ctrl/⌘+Enter 发送。组合出的内容存成 Markdown，发送时分别转成净化后的
+    // HTML（存进 `ChatMessage.rich_html`，给聊天气泡渲染）和纯文本（存进
+    // `text`，发给模型，不带格式标记）
+    let mut rich_mode = use_signal(|| false);
+    let has_result_table = messages.read().iter().any(|m| m.table.is_some());
+
+    let insert_snippet = move |snippet: &'static str| {
+        input_text.with_mut(|t| {
+            if !t.is_empty() && !t.ends_with('\n') && !t.ends_with(' ') {
+                t.push(' ');
+            }
+            t.push_str(snippet);
+        });
+    };
+
+    let paste_table = move |_| {
+        spawn(async move {
+            let mut clip = eval(
+                r#"try {
+                    const t = await navigator.clipboard.readText();
+                    dioxus.send(t);
+                } catch (e) {
+                    dioxus.send("");
+                }"#,
+            );
+            if let Ok(text) = clip.recv::<String>().await {
+                if let Some(table) = tsv_to_markdown_table(&text) {
+                    input_text.with_mut(|t| {
+                        if !t.is_empty() && !t.ends_with('\n') {
+                            t.push('\n');
+                        }
+                        t.push_str(&table);
+                    });
+                }
+            }
+        });
+    };
+
+    let toggle_mic = move |_| {
+        if is_recording() {
+            is_recording.set(false);
+            let Some(mut handle) = recorder_handle.write().take() else {
+                return;
+            };
+            // 给 JS 侧发个信号结束 await dioxus.recv()，录音机才会真正停下来
+            let _ = handle.send(());
+            spawn(async move {
+                let Ok(base64_audio) = handle.recv::<String>().await else {
+                    return;
+                };
+                let Ok(audio_bytes) = general_purpose::STANDARD.decode(base64_audio) else {
+                    println!("⚠️ 录音数据解码失败");
+                    return;
+                };
+                let profile = config.read().active_profile();
+                match voice::transcribe(&profile, audio_bytes).await {
+                    Ok(text) => input_text.set(text),
+                    Err(e) => println!("⚠️ 语音转写失败: {}", e),
+                }
+            });
+        } else {
+            is_recording.set(true);
+            recorder_handle.set(Some(eval(MIC_RECORDER_JS)));
+        }
+    };
 
     let mut handle_send = move || {
         if input_text.read().trim().is_empty() {
             return;
         }
 
-        let user_prompt = input_text.read().clone();
+        let raw_input = input_text.read().clone();
+        // 富文本模式下，输入框里存的是 Markdown：净化后的 HTML 留给聊天气泡
+        // 渲染，纯文本（去掉 `**`/`` ` ``/`- ` 等标记）才是发给模型和存进历史
+        // 的 `user_prompt`——普通模式下两者没区别，直接用原文
+        let (user_prompt, rich_html) = if rich_mode() {
+            (
+                markdown_to_plain_text(&raw_input),
+                Some(markdown_to_html(&raw_input)),
+            )
+        } else {
+            (raw_input.clone(), None)
+        };
         let file_path = last_file_path.read().clone();
+        // 把 SheetView 里框选的区域转成一句范围提示，附加给 AI 的 query；
+        // 聊天气泡里只展示用户原话，不把这句提示也打进去
+        let range_hint = selected_range().map(|r| r.describe());
 
         let new_id = messages.read().len();
         messages.write().push(ChatMessage {
-            id: new_id,
-            text: user_prompt.clone(),
-            is_user: true,
-            table: None,
-            temp_id: None,
-            status: crate::models::ActionStatus::None,
-            image: None,
+            rich_html,
+            ..ChatMessage::new(new_id, user_prompt.clone(), true)
         });
         input_text.set(String::new());
 
@@ -40,21 +178,17 @@ pub fn InputArea(
             // Basic check
             if file_path.is_empty() {
                 let err_id = messages.read().len();
-                messages.write().push(ChatMessage {
-                    id: err_id,
-                    text: "⚠️ 请先拖入一个 Excel 文件（哪怕是空文件），我才能开始工作。".into(),
-                    is_user: false,
-                    table: None,
-                    temp_id: None,
-                    status: crate::models::ActionStatus::None,
-                    image: None,
-                });
+                messages.write().push(ChatMessage::new(
+                    err_id,
+                    "⚠️ 请先拖入一个 Excel 文件（哪怕是空文件），我才能开始工作。",
+                    false,
+                ));
                 is_loading.set(false);
                 return;
             }
 
             // Read config
-            let (key, url, model) = {
+            let app_config = {
                 let cfg = config.read();
                 let active_profile = cfg
                     .active_profile_id
@@ -62,21 +196,14 @@ pub fn InputArea(
                     .and_then(|id| cfg.profiles.iter().find(|p| &p.id == id));
 
                 match active_profile {
-                    Some(p) if !p.api_key.is_empty() => {
-                        (p.api_key.clone(), p.base_url.clone(), p.model_id.clone())
-                    }
+                    Some(p) if !p.api_key.is_empty() => cfg.clone(),
                     _ => {
                         let err_id = messages.read().len();
-                        messages.write().push(ChatMessage {
-                            id: err_id,
-                            text: "❌ 配置错误：请在设置中选中一个模型，并确保 API Key 不为空。"
-                                .into(),
-                            is_user: false,
-                            table: None,
-                            temp_id: None,
-                            status: crate::models::ActionStatus::None,
-                            image: None,
-                        });
+                        messages.write().push(ChatMessage::new(
+                            err_id,
+                            "❌ 配置错误：请在设置中选中一个模型，并确保 API Key 不为空。",
+                            false,
+                        ));
                         is_loading.set(false);
                         return;
                     }
@@ -84,177 +211,163 @@ pub fn InputArea(
             };
 
             // Prepare backend task
+            let query_for_ai = match &range_hint {
+                Some(desc) => format!(
+                    "{}\n\n(用户已在表格中框选单元格区域 {}，如果需要请把代码范围限定在该区域)",
+                    user_prompt, desc
+                ),
+                None => user_prompt.clone(),
+            };
             let file_path_clone = file_path.clone();
-            let columns_result =
-                task::spawn_blocking(move || python::get_excel_columns(&file_path_clone)).await;
+            let query_clone = query_for_ai.clone();
+            let columns_result = task::spawn_blocking(move || {
+                python::get_excel_columns(&file_path_clone, &query_clone)
+            })
+            .await;
 
             let columns = match columns_result {
                 Ok(cols) => cols,
                 Err(_) => {
                     let err_id = messages.read().len();
-                    messages.write().push(ChatMessage {
-                        id: err_id,
-                        text: "❌ 系统错误: 线程崩溃".into(),
-                        is_user: false,
-                        table: None,
-                        temp_id: None,
-                        status: crate::models::ActionStatus::None,
-                        image: None,
-                    });
+                    messages
+                        .write()
+                        .push(ChatMessage::new(err_id, "❌ 系统错误: 线程崩溃", false));
                     is_loading.set(false);
                     return;
                 }
             };
 
-            /* Auto fix complie error.. loop */
-            // Max retry times
-            const MAX_RETRIES: usize = 3;
-            // Current prompt, init prompt is user input
-            let mut current_prompt = user_prompt.clone();
-            // Is success
-            let mut success = false;
-
-            for attempt in 0..MAX_RETRIES {
-                let ai_result = ai::call_ai(
-                    key.clone(),
-                    url.clone(),
-                    model.clone(),
-                    current_prompt.clone(),
-                    columns.clone(),
-                )
-                .await;
+            // 最多自动修复几次：生成的代码报错了，就把报错喂回去让模型重新生成
+            const MAX_REPAIR_ATTEMPTS: usize = 3;
+            // 这一轮会话记忆：把当前已有的消息记录（不含还没写回的最新一条）
+            // 作为滚动窗口传给 call_ai，让模型记得之前做过什么
+            let history_snapshot = messages.read().clone();
 
-                match ai_result {
-                    Ok(reply) => {
-                        if reply.reply_type == "code" {
-                            let file_path_for_exec = file_path.clone();
-                            let code_for_exec = reply.content.clone();
+            // 闭环自愈现在下沉到了 call_ai 内部（见 services::ai::call_ai 的
+            // `auto_execute` 模式）：生成代码、执行、失败就带着报错重新生成，
+            // 这里只需要调用一次，再把返回的修复轨迹和最终结果渲染出来。
+            // `auto_execute` 是否开启取决于用户在输入框旁勾的"自动执行代码"
+            // 开关（见下面的 checkbox），关闭时 call_ai 只生成代码不执行，
+            // 走 `WaitingConfirmation` 由用户点"立即执行"确认。
+            let auto_execute = app_config.auto_execute_code;
+            // 先占好这一轮 AI 回复的第一个坑位：纯闲聊分支会直接把 token 流进
+            // 这条占位消息（见 `ai::call_ai` 顶部的流式闲聊说明）；走代码生成
+            // 路径的话，下面会原地改写成修复轨迹/最终结果的第一条，不会露出
+            // "思考中"气泡。
+            let chat_msg_id = messages.read().len();
+            messages.write().push(ChatMessage::loading(chat_msg_id));
+            let ai_result = ai::call_ai(
+                &app_config,
+                &file_path,
+                &query_for_ai,
+                &columns,
+                &history_snapshot,
+                auto_execute,
+                MAX_REPAIR_ATTEMPTS,
+                messages,
+                chat_msg_id,
+            )
+            .await;
 
-                            // Gnerate uuid
-                            let operation_id = Uuid::new_v4().to_string();
-                            let op_id_for_exec = operation_id.clone();
+            // 按顺序把这一轮要展示的消息依次"落位"：第一条直接原地改写占位消息
+            // (`chat_msg_id`，流式闲聊已经用过的同一个坑位)，后面的才是真正新增
+            // ——这样修复轨迹、最终结果的视觉顺序跟以前一样，也不会留下一条永远
+            // "思考中"的占位气泡。
+            let place = |id: usize, built: ChatMessage| {
+                let mut w = messages.write();
+                if id < w.len() {
+                    w[id] = built;
+                } else {
+                    w.push(built);
+                }
+            };
 
-                            // Execute Python Backend
-                            let exec_join = task::spawn_blocking(move || {
-                                python::run_python_code(
-                                    &file_path_for_exec,
-                                    &code_for_exec,
-                                    &op_id_for_exec,
-                                )
-                            })
-                            .await;
+            match ai_result {
+                Ok(reply) => {
+                    let mut next_id = chat_msg_id;
 
-                            match exec_join {
-                                Ok(json_str) => {
-                                    // Prase the python return JSON
-                                    match serde_json::from_str::<PyExecResult>(&json_str) {
-                                        Ok(res) => {
-                                            if res.status == "error" {
-                                                // Error, try again
-                                                println!(
-                                                    "尝试 #{} 失败: {}",
-                                                    attempt + 1,
-                                                    res.message
-                                                );
-                                                current_prompt = format!(
-                                                                "你生成的代码运行报错了。\n\n刚才的代码:\n{}\n\n报错信息:\n{}\n\n请分析错误原因，并重新生成修正后的完整代码。",
-                                                                reply.content,
-                                                                res.message
-                                                            );
+                    // 每一次失败重试都先作为一条消息展示出来，形成可见的修复轨迹
+                    for attempt in &reply.repair_log {
+                        let id = next_id;
+                        next_id += 1;
+                        place(
+                            id,
+                            ChatMessage::new(
+                                id,
+                                format!(
+                                    "⚠️ 第 {} 次尝试执行失败，正在自动修复:\n{}",
+                                    attempt.attempt, attempt.error
+                                ),
+                                false,
+                            ),
+                        );
+                    }
 
-                                                if attempt == MAX_RETRIES - 1 {
-                                                    let err_id = messages.read().len();
-                                                    messages.write().push(ChatMessage {
-                                                        id: err_id,
-                                                        text: format!(
-                                                            "🤯 自动修复失败。\n最后报错:\n{}",
-                                                            res.message
-                                                        ),
-                                                        is_user: false,
-                                                        table: None,
-                                                        temp_id: None,
-                                                        status: crate::models::ActionStatus::None,
-                                                        image: None,
-                                                    });
-                                                }
-                                            } else {
-                                                // Success, show result and table
-                                                let final_reply = format!(
-                                                    "🔧 执行代码:\n{}\n\n{}",
-                                                    reply.content, res.message
-                                                );
-                                                let ai_id = messages.read().len();
+                    if reply.reply_type == "code" {
+                        let id = next_id;
+                        match reply.exec_result {
+                            Some(res) if res.status == "error" => {
+                                place(
+                                    id,
+                                    ChatMessage::new(
+                                        id,
+                                        format!(
+                                            "🤯 自动修复失败。\n最后一版代码:\n{}\n\n最后报错:\n{}",
+                                            reply.content, res.message
+                                        ),
+                                        false,
+                                    ),
+                                );
+                            }
+                            Some(res) => {
+                                // auto_execute 开启时走到这里：代码已经自动跑完了，
+                                // 展示结果和表格，没有待确认的代码
+                                let operation_id = Uuid::new_v4().to_string();
+                                let final_reply =
+                                    format!("🔧 执行代码:\n{}\n\n{}", reply.content, res.message);
 
-                                                messages.write().push(ChatMessage {
-                                                    id: ai_id,
-                                                    text: final_reply,
-                                                    is_user: false,
-                                                    table: res.preview,
-                                                    temp_id: Some(operation_id.clone()),
-                                                    status: crate::models::ActionStatus::Pending,
-                                                    image: None,
-                                                });
-                                                break;
-                                            }
-                                        }
-                                        Err(e) => {
-                                            // Prase JSON Failed
-                                            let err_id = messages.read().len();
-                                            messages.write().push(ChatMessage {
-                                                id: err_id,
-                                                text: format!("❌ 内部通讯错误: {}", e),
-                                                is_user: false,
-                                                table: None,
-                                                temp_id: None,
-                                                status: crate::models::ActionStatus::None,
-                                                image: None,
-                                            });
-                                            break;
-                                        }
-                                    }
-                                }
-                                Err(_) => {
-                                    let err_id = messages.read().len();
-                                    messages.write().push(ChatMessage {
-                                        id: err_id,
-                                        text: "❌ Python 线程崩溃".into(),
-                                        is_user: false,
-                                        table: None,
-                                        temp_id: None,
-                                        status: crate::models::ActionStatus::None,
-                                        image: None,
-                                    });
-                                    break;
-                                }
+                                place(
+                                    id,
+                                    ChatMessage {
+                                        table: res.preview,
+                                        temp_id: Some(operation_id),
+                                        status: crate::models::ActionStatus::Success,
+                                        image: res.image,
+                                        ..ChatMessage::new(id, final_reply, false)
+                                    },
+                                );
+                            }
+                            None => {
+                                // auto_execute 关闭时走到这里：只生成了代码，还没有执行，
+                                // 等用户在气泡上点"立即执行"确认（见 `on_confirm`/`pending_code`）
+                                let code = reply.content.clone();
+                                place(
+                                    id,
+                                    ChatMessage {
+                                        status: crate::models::ActionStatus::WaitingConfirmation,
+                                        pending_code: Some(code),
+                                        ..ChatMessage::new(
+                                            id,
+                                            format!(
+                                                "🔧 已生成代码，点击运行执行:\n{}",
+                                                reply.content
+                                            ),
+                                            false,
+                                        )
+                                    },
+                                );
                             }
-                        } else {
-                            // Chat
-                            let ai_id = messages.read().len();
-                            messages.write().push(ChatMessage {
-                                id: ai_id,
-                                text: reply.content,
-                                is_user: false,
-                                table: None,
-                                temp_id: None,
-                                status: crate::models::ActionStatus::None,
-                                image: None,
-                            });
-                            break;
                         }
                     }
-                    Err(err) => {
-                        let err_id = messages.read().len();
-                        messages.write().push(ChatMessage {
-                            id: err_id,
-                            text: format!("❌ 网络请求失败: {}", err),
-                            is_user: false,
-                            table: None,
-                            temp_id: None,
-                            status: crate::models::ActionStatus::None,
-                            image: None,
-                        });
-                        break;
-                    }
+                    // reply_type == "chat" 的内容已经在 `ai::call_ai` 内部通过
+                    // `stream_chat_completion` 边生成边写进 `messages[chat_msg_id]`
+                    // 了（且没有修复轨迹要插在前面），这里不需要再落位一次。
+                }
+                Err(err) => {
+                    place(
+                        chat_msg_id,
+                        ChatMessage::new(chat_msg_id, format!("❌ 网络请求失败: {}", err), false),
+                    );
                 }
             }
 
@@ -262,20 +375,170 @@ pub fn InputArea(
         });
     };
 
+    // 把当前会话里所有带结果表的消息各自解析成一张 sheet，打成一个多 sheet 的
+    // .xlsx 存盘——解析失败的单条消息直接跳过，不因为一条格式古怪的表拖垮整次导出
+    let handle_export_all = move |_| {
+        let table_msgs: Vec<(usize, String)> = messages
+            .read()
+            .iter()
+            .filter_map(|m| m.table.clone().map(|t| (m.id, t)))
+            .collect();
+        if table_msgs.is_empty() {
+            return;
+        }
+
+        spawn(async move {
+            let Some(save_path) = rfd::AsyncFileDialog::new()
+                .set_file_name("分析结果.xlsx")
+                .add_filter("Excel", &["xlsx"])
+                .save_file()
+                .await
+            else {
+                return;
+            };
+
+            let sheets: Vec<ExportSheet> = table_msgs
+                .iter()
+                .filter_map(|(id, html)| export::parse_html_table(html, &format!("结果{}", id)))
+                .collect();
+
+            let sheet_count = sheets.len();
+            let out_path = save_path.path().to_string_lossy().to_string();
+            let result =
+                task::spawn_blocking(move || export::export_sheets_to_xlsx(&sheets, &out_path))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("导出线程崩溃: {}", e)));
+
+            let msg_id = messages.read().len();
+            let text = match result {
+                Ok(()) => format!(
+                    "✅ 已导出 {} 张结果表到 {}",
+                    sheet_count,
+                    save_path.path().display()
+                ),
+                Err(e) => format!("❌ 导出失败: {}", e),
+            };
+            messages.write().push(ChatMessage::new(msg_id, text, false));
+        });
+    };
+
     rsx! {
         div { class: "input-container",
-            input {
-                class: "chat-input",
-                placeholder: "输入需求...",
-                value: "{input_text}",
-                oninput: move |evt| input_text.set(evt.value()),
-                disabled: is_loading(),
-                onkeydown: move |evt| {
-                    if evt.key() == Key::Enter && !evt.modifiers().contains(Modifiers::SHIFT) {
-                        evt.prevent_default();
-                        handle_send();
+            if let Some(range) = selected_range() {
+                div {
+                    class: "selected-range-chip",
+                    style: "display: flex; align-items: center; gap: 6px; font-size: 12px; color: #666; margin-bottom: 4px;",
+                    span { "📍 已选中 {range.describe()}，将随消息发送给 AI" }
+                    button {
+                        style: "border: none; background: none; color: #999; cursor: pointer;",
+                        onclick: move |_| selected_range.set(None),
+                        "✕"
+                    }
+                }
+            }
+            if rich_mode() {
+                div {
+                    class: "rich-toolbar",
+                    style: "display: flex; align-items: center; gap: 6px; margin-bottom: 4px;",
+                    button {
+                        r#type: "button",
+                        title: "加粗",
+                        onclick: move |_| insert_snippet("**粗体文字**"),
+                        "B"
+                    }
+                    button {
+                        r#type: "button",
+                        title: "行内代码（适合标记列名）",
+                        onclick: move |_| insert_snippet("`列名`"),
+                        "</>"
+                    }
+                    button {
+                        r#type: "button",
+                        title: "插入步骤列表",
+                        onclick: move |_| insert_snippet("\n- 步骤一\n- 步骤二"),
+                        "• 列表"
+                    }
+                    button {
+                        r#type: "button",
+                        title: "把剪贴板里的表格片段转成 Markdown 表格插入",
+                        onclick: paste_table,
+                        "📋 表格"
                     }
-                },
+                }
+                textarea {
+                    class: "chat-input rich-input",
+                    placeholder: "输入需求...（支持 **加粗**、`行内代码`、- 列表；Ctrl/⌘+Enter 发送）",
+                    rows: 3,
+                    value: "{input_text}",
+                    oninput: move |evt| input_text.set(evt.value()),
+                    disabled: is_loading(),
+                    onkeydown: move |evt| {
+                        let sends = evt.key() == Key::Enter
+                            && (evt.modifiers().contains(Modifiers::CONTROL)
+                                || evt.modifiers().contains(Modifiers::META));
+                        if sends {
+                            evt.prevent_default();
+                            handle_send();
+                        }
+                    },
+                }
+            } else {
+                input {
+                    class: "chat-input",
+                    placeholder: "输入需求...",
+                    value: "{input_text}",
+                    oninput: move |evt| input_text.set(evt.value()),
+                    disabled: is_loading(),
+                    onkeydown: move |evt| {
+                        if evt.key() == Key::Enter && !evt.modifiers().contains(Modifiers::SHIFT) {
+                            evt.prevent_default();
+                            handle_send();
+                        }
+                    },
+                }
+            }
+            button {
+                class: "rich-mode-toggle",
+                style: if rich_mode() { "color: #0d6efd;" } else { "" },
+                title: "富文本模式：多行编辑 + 加粗/代码/列表/表格",
+                onclick: move |_| rich_mode.set(!rich_mode()),
+                "✏️"
+            }
+            button {
+                class: "mic-btn",
+                style: if is_recording() { "color: #dc3545;" } else { "" },
+                title: "语音输入",
+                onclick: toggle_mic,
+                if is_recording() { "⏺ 录音中…" } else { "🎙" }
+            }
+            label {
+                class: "auto-speak-toggle",
+                style: "display: flex; align-items: center; gap: 4px; font-size: 12px; color: #666; margin: 0 8px;",
+                input {
+                    r#type: "checkbox",
+                    checked: config.read().auto_speak,
+                    onchange: move |evt| config.write().auto_speak = evt.checked(),
+                }
+                "自动朗读"
+            }
+            label {
+                class: "auto-execute-toggle",
+                title: "关闭时生成的代码只展示，需要手动点运行才会执行",
+                style: "display: flex; align-items: center; gap: 4px; font-size: 12px; color: #666; margin: 0 8px;",
+                input {
+                    r#type: "checkbox",
+                    checked: config.read().auto_execute_code,
+                    onchange: move |evt| config.write().auto_execute_code = evt.checked(),
+                }
+                "自动执行代码"
+            }
+            if has_result_table {
+                button {
+                    class: "export-btn",
+                    title: "把会话里所有结果表导出成一个多 sheet 的 .xlsx",
+                    onclick: handle_export_all,
+                    "📤 导出结果"
+                }
             }
             button { class: "send-btn", onclick: move |_| handle_send(), "发送" }
         }
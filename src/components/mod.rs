@@ -0,0 +1,13 @@
+pub mod chat_view;
+pub mod context_menu;
+pub mod conversation_list;
+pub mod data_table;
+pub mod dock_capsule;
+pub mod global_hotkeys;
+pub mod image_lightbox;
+pub mod input_area;
+pub mod settings;
+pub mod sheet_view;
+pub mod sidebar;
+pub mod title_bar;
+pub mod widget_ball;
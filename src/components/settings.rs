@@ -1,23 +1,108 @@
-use crate::models::{AppConfig, ModelProfile};
+use crate::models::{AppConfig, FieldMapping, FieldType, ImportSchema, ModelProfile, Theme};
 use crate::services::config::save_config;
+use crate::services::{ai, import_schema};
 use dioxus::prelude::*;
+use std::collections::HashMap;
 use std::time::Duration;
+use tokio::task;
+
+/// 某个模型配置最近一次"测试连接"的结果，只在内存里存一份，不落盘——重启之后
+/// 网络状况可能已经变了，没必要把一次性的测试结果当成持久配置
+#[derive(Clone, PartialEq)]
+enum ConnectionTestStatus {
+    Testing,
+    Success { latency_ms: u128 },
+    Failed(String),
+}
+
+/// API Base URL 粗校验：只看"看起来像不像一个 http(s) 地址"，不做真正的 URL 解析
+fn validate_base_url(url: &str) -> Option<&'static str> {
+    let url = url.trim();
+    if url.is_empty() {
+        Some("不能为空")
+    } else if !(url.starts_with("http://") || url.starts_with("https://")) {
+        Some("需要以 http:// 或 https:// 开头")
+    } else {
+        None
+    }
+}
+
+fn validate_model_id(model_id: &str) -> Option<&'static str> {
+    if model_id.trim().is_empty() {
+        Some("不能为空")
+    } else {
+        None
+    }
+}
+
+/// 把一次 `onkeydown` 按出来的组合键格式化成 `global-hotkey` crate 认识的
+/// "Ctrl+Alt+Space" 这种写法；要求至少带一个修饰键，纯单键太容易跟日常打字冲突，
+/// 单独按下修饰键本身（还没配上别的键）也不算数，等用户按出完整组合
+fn format_hotkey_combo(evt: &KeyboardData) -> Option<String> {
+    let key = evt.key();
+    if matches!(key, Key::Control | Key::Alt | Key::Shift | Key::Meta) {
+        return None;
+    }
+
+    let modifiers = evt.modifiers();
+    let mut parts = Vec::new();
+    if modifiers.contains(Modifiers::CONTROL) {
+        parts.push("Ctrl".to_string());
+    }
+    if modifiers.contains(Modifiers::ALT) {
+        parts.push("Alt".to_string());
+    }
+    if modifiers.contains(Modifiers::SHIFT) {
+        parts.push("Shift".to_string());
+    }
+    if modifiers.contains(Modifiers::META) {
+        parts.push("Super".to_string());
+    }
+    if parts.is_empty() {
+        return None;
+    }
+
+    // `code()` 给出的是平台无关的物理按键标识（"Space"、"KeyD"、"Escape"……），
+    // 正好跟 `global-hotkey` 解析器期望的 token 对得上，不用再自己翻译 `key()`
+    parts.push(evt.code().to_string());
+    Some(parts.join("+"))
+}
+
+/// API Key 校验：真正判断 Key 合不合法只能靠"测试连接"，这里最多提醒一下常见的
+/// 填错情况（没填 / 大概率抄错了格式）
+fn validate_api_key(api_key: &str) -> Option<&'static str> {
+    let api_key = api_key.trim();
+    if api_key.is_empty() {
+        Some("不能为空")
+    } else if !api_key.starts_with("sk-") {
+        Some("提示：多数服务商的 Key 以 sk- 开头，请确认没抄错")
+    } else {
+        None
+    }
+}
 
 #[component]
 pub fn Settings(
     config: Signal<AppConfig>,
     on_close: EventHandler<()>, // 这里的 on_close 逻辑已经在 main.rs 里被我们改造成带延迟的了
 ) -> Element {
-    let mut editing_profile = use_signal(|| ModelProfile::new());
+    // 打开面板时默认选中"当前使用中"的配置来编辑；后续侧栏点选只改这个信号，
+    // 不会再顺带改 `config.active_profile_id`——选中去编辑和设为当前使用是
+    // 两件事，见下面侧栏 `onclick` 和"设为当前使用"按钮的说明
+    let mut editing_profile = use_signal(|| {
+        let cfg = config.read();
+        cfg.active_profile_id
+            .as_ref()
+            .and_then(|id| cfg.profiles.iter().find(|p| &p.id == id))
+            .or_else(|| cfg.profiles.first())
+            .cloned()
+            .unwrap_or_else(ModelProfile::new)
+    });
     let mut anim_ready = use_signal(|| false);
+    // 按 profile id 存最近一次测试连接的结果；只在当前这次运行里有意义
+    let mut test_results = use_signal(HashMap::<String, ConnectionTestStatus>::new);
 
     use_effect(move || {
-        let cfg = config.read();
-        if let Some(active_id) = &cfg.active_profile_id {
-            if let Some(profile) = cfg.profiles.iter().find(|p| &p.id == active_id) {
-                editing_profile.set(profile.clone());
-            }
-        }
         // 设置界面打开时的淡入延迟
         spawn(async move {
             tokio::time::sleep(Duration::from_millis(200)).await;
@@ -42,13 +127,36 @@ pub fn Settings(
     let mut add_profile = move || {
         let mut current_config = config.read().clone();
         let new_profile = ModelProfile::new();
-        let new_id = new_profile.id.clone();
+        // 新配置只是加进列表、选中来编辑，不直接设为当前使用——一个还没填 Key、
+        // 更没测过连接的空白配置不该变成当前使用的模型
+        editing_profile.set(new_profile.clone());
         current_config.profiles.push(new_profile);
-        current_config.active_profile_id = Some(new_id);
         config.set(current_config.clone());
         save_config(&current_config);
     };
 
+    // 外观：跟 profiles 一样是"改一下马上存盘"，没有单独的保存按钮
+    let mut set_theme = move |theme: Theme| {
+        let mut current_config = config.read().clone();
+        current_config.theme = theme;
+        config.set(current_config.clone());
+        save_config(&current_config);
+    };
+
+    let run_test_connection = move |profile: ModelProfile| {
+        let profile_id = profile.id.clone();
+        test_results
+            .write()
+            .insert(profile_id.clone(), ConnectionTestStatus::Testing);
+        spawn(async move {
+            let outcome = match ai::test_connection(&profile).await {
+                Ok(latency_ms) => ConnectionTestStatus::Success { latency_ms },
+                Err(e) => ConnectionTestStatus::Failed(e.to_string()),
+            };
+            test_results.write().insert(profile_id, outcome);
+        });
+    };
+
     let mut delete_profile = move |id: String| {
         let mut current_config = config.read().clone();
         if current_config.profiles.len() <= 1 {
@@ -64,6 +172,94 @@ pub fn Settings(
         save_config(&current_config);
     };
 
+    // 导入字段映射：和 profiles 同一套"列表 + 点选即编辑"模式，active_import_schema_id
+    // 既决定编辑面板显示哪一份，也决定文件导入时实际套用哪一份
+    let mut add_schema = move || {
+        let mut current_config = config.read().clone();
+        let new_schema = ImportSchema::new();
+        let new_id = new_schema.id.clone();
+        current_config.import_schemas.push(new_schema);
+        current_config.active_import_schema_id = Some(new_id);
+        config.set(current_config.clone());
+        save_config(&current_config);
+    };
+
+    let select_schema = move |id: String| {
+        let mut current_config = config.read().clone();
+        current_config.active_import_schema_id = Some(id);
+        config.set(current_config.clone());
+        save_config(&current_config);
+    };
+
+    let delete_schema = move |id: String| {
+        let mut current_config = config.read().clone();
+        current_config.import_schemas.retain(|s| s.id != id);
+        if current_config.active_import_schema_id.as_ref() == Some(&id) {
+            current_config.active_import_schema_id =
+                current_config.import_schemas.first().map(|s| s.id.clone());
+        }
+        config.set(current_config.clone());
+        save_config(&current_config);
+    };
+
+    // 就地修改当前激活 schema 的小 helper，省得每个字段的 onXXX 里都重复一遍
+    // "读配置 -> 找到激活项 -> 改 -> 写回 -> 存盘"
+    let mut update_active_schema = move |f: Box<dyn FnOnce(&mut ImportSchema)>| {
+        let mut current_config = config.read().clone();
+        let Some(active_id) = current_config.active_import_schema_id.clone() else {
+            return;
+        };
+        if let Some(schema) = current_config
+            .import_schemas
+            .iter_mut()
+            .find(|s| s.id == active_id)
+        {
+            f(schema);
+        }
+        config.set(current_config.clone());
+        save_config(&current_config);
+    };
+
+    let mut add_field = move || {
+        update_active_schema(Box::new(|schema| schema.fields.push(FieldMapping::new())));
+    };
+    let mut remove_field = move |idx: usize| {
+        update_active_schema(Box::new(move |schema| {
+            if idx < schema.fields.len() {
+                schema.fields.remove(idx);
+            }
+        }));
+    };
+
+    let download_template = move |_| {
+        let Some(schema) = config.read().active_import_schema().cloned() else {
+            return;
+        };
+        spawn(async move {
+            let Some(save_path) = rfd::AsyncFileDialog::new()
+                .set_file_name(format!("{}_模板.xlsx", schema.name))
+                .add_filter("Excel", &["xlsx"])
+                .save_file()
+                .await
+            else {
+                return;
+            };
+            let out_path = save_path.path().to_string_lossy().to_string();
+            let result = task::spawn_blocking(move || {
+                import_schema::generate_template_xlsx(&schema, &out_path)
+            })
+            .await;
+            if let Err(e) = result.unwrap_or_else(|e| Err(format!("模板生成线程崩溃: {}", e)))
+            {
+                println!("⚠️ 下载模板失败: {}", e);
+            }
+        });
+    };
+
+    let schemas = config.read().import_schemas.clone();
+    let active_schema_id = config.read().active_import_schema_id.clone();
+    let active_schema = config.read().active_import_schema().cloned();
+
     let profiles = config.read().profiles.clone();
     let active_id = config.read().active_profile_id.clone();
     let profiles_count = profiles.len();
@@ -74,6 +270,15 @@ pub fn Settings(
     };
 
     rsx! {
+        // 颜色跟着 `main.rs` 写在 <html data-theme="..."> 上的属性走，和
+        // `DockCapsule`、`SheetView` 共用同一套主题子系统
+        document::Style { r#"
+            [data-theme="dark"] .settings-panel {{ background: #1e1e1e; color: #ddd; }}
+            [data-theme="dark"] .comic-input {{ background: #2a2a2a; color: #ddd; border-color: #444; }}
+            [data-theme="dark"] .model-item {{ background: #2a2a2a; border-color: #444; }}
+            [data-theme="dark"] .model-item.active {{ background: #2d4a66; }}
+        "# }
+
         div {
             class: "settings-panel",
             style: "{opacity_style} transition: opacity 0.2s ease;",
@@ -91,27 +296,82 @@ pub fn Settings(
 
                 div { class: "settings-body",
                     div { class: "settings-sidebar",
+                        div { class: "sidebar-label", "外观" }
+                        div { class: "theme-switcher",
+                            for theme in [Theme::Light, Theme::Dark, Theme::FollowSystem] {
+                                div {
+                                    key: "{theme:?}",
+                                    class: if config.read().theme == theme { "model-item active" } else { "model-item" },
+                                    onclick: move |_| set_theme(theme),
+                                    "{theme.label()}"
+                                }
+                            }
+                        }
+
+                        div { class: "sidebar-label", "全局快捷键" }
+                        div { class: "form-group",
+                            label { "召唤窗口（胶囊 → 聊天）" }
+                            input {
+                                class: "comic-input",
+                                readonly: true,
+                                value: "{config.read().hotkey_summon}",
+                                placeholder: "按下快捷键…",
+                                onkeydown: move |evt: Event<KeyboardData>| {
+                                    evt.prevent_default();
+                                    if let Some(combo) = format_hotkey_combo(&evt) {
+                                        let mut cfg = config.read().clone();
+                                        cfg.hotkey_summon = combo;
+                                        config.set(cfg.clone());
+                                        save_config(&cfg);
+                                    }
+                                },
+                            }
+                        }
+                        div { class: "form-group",
+                            label { "收回窗口（聊天 → 胶囊）" }
+                            input {
+                                class: "comic-input",
+                                readonly: true,
+                                value: "{config.read().hotkey_dismiss}",
+                                placeholder: "按下快捷键…",
+                                onkeydown: move |evt: Event<KeyboardData>| {
+                                    evt.prevent_default();
+                                    if let Some(combo) = format_hotkey_combo(&evt) {
+                                        let mut cfg = config.read().clone();
+                                        cfg.hotkey_dismiss = combo;
+                                        config.set(cfg.clone());
+                                        save_config(&cfg);
+                                    }
+                                },
+                            }
+                        }
+
                         div { class: "sidebar-label", "可用模型" }
                         {
                             profiles
                                 .into_iter()
                                 .map(|profile| {
                                     let p_id = profile.id.clone();
-                                    let id_for_click = profile.id.clone();
+                                    let profile_for_click = profile.clone();
                                     let id_for_del = profile.id.clone();
+                                    // 侧栏的高亮/点击只代表"正在编辑哪一份"，不再等同于"当前使用哪一份"
+                                    // ——真正生效的模型由 `active_id` 决定，旁边单独标一个"使用中"
+                                    let is_editing = editing_profile.read().id == p_id;
                                     let is_active = Some(&p_id) == active_id.as_ref();
                                     rsx! {
                                         div {
                                             key: "{p_id}",
-                                            class: if is_active { "model-item active" } else { "model-item" },
+                                            class: if is_editing { "model-item active" } else { "model-item" },
                                             onclick: move |_| {
-                                                let mut cfg = config.read().clone();
-                                                cfg.active_profile_id = Some(id_for_click.clone());
-                                                config.set(cfg.clone());
-                                                save_config(&cfg);
+                                                editing_profile.set(profile_for_click.clone());
                                             },
                                             div { style: "display: flex; justify-content: space-between; align-items: center;",
-                                                div { class: "model-name", "{profile.name}" }
+                                                div { class: "model-name",
+                                                    "{profile.name}"
+                                                    if is_active {
+                                                        span { style: "margin-left: 6px; font-size: 11px; color: #2d7a2d;", "· 使用中" }
+                                                    }
+                                                }
                                                 if profiles_count > 1 {
                                                     div {
                                                         class: "del-btn",
@@ -125,6 +385,19 @@ pub fn Settings(
                                                 }
                                             }
                                             div { class: "model-desc", "{profile.model_id}" }
+                                            if let Some(status) = test_results.read().get(&p_id) {
+                                                match status {
+                                                    ConnectionTestStatus::Testing => rsx! {
+                                                        div { class: "model-test-badge testing", "测试中…" }
+                                                    },
+                                                    ConnectionTestStatus::Success { latency_ms } => rsx! {
+                                                        div { class: "model-test-badge success", "✓ 已连接 ({latency_ms}ms)" }
+                                                    },
+                                                    ConnectionTestStatus::Failed(_) => rsx! {
+                                                        div { class: "model-test-badge failed", "✗ 连接失败" }
+                                                    },
+                                                }
+                                            }
                                         }
                                     }
                                 })
@@ -138,6 +411,40 @@ pub fn Settings(
 
                     div { class: "settings-content",
                         div { class: "form-header", "编辑详情" }
+                        // 侧栏点击只是"选中来编辑"，不会直接切换当前使用的配置——这里的
+                        // "设为当前使用"按钮才是真正的激活入口，且必须本次运行测试连接
+                        // 成功过才能点，没测试通过的配置不可能变成当前使用的模型
+                        {
+                            let editing_id = editing_profile.read().id.clone();
+                            let activate_id = editing_id.clone();
+                            let is_current = active_id == Some(editing_id.clone());
+                            let tested_ok = matches!(
+                                test_results.read().get(&editing_id),
+                                Some(ConnectionTestStatus::Success { .. }),
+                            );
+                            rsx! {
+                                div { class: "form-group", style: "display: flex; align-items: center; gap: 8px;",
+                                    if is_current {
+                                        div { class: "field-hint success", "✓ 当前正在使用这份配置" }
+                                    } else {
+                                        button {
+                                            class: "add-model-btn",
+                                            disabled: !tested_ok,
+                                            onclick: move |_| {
+                                                let mut cfg = config.read().clone();
+                                                cfg.active_profile_id = Some(activate_id.clone());
+                                                config.set(cfg.clone());
+                                                save_config(&cfg);
+                                            },
+                                            "设为当前使用"
+                                        }
+                                        if !tested_ok {
+                                            div { class: "field-hint", "⚠️ 需要先测试连接成功，才能设为当前使用" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
                         div { class: "form-group",
                             label { "配置名称 (别名)" }
                             input {
@@ -160,6 +467,9 @@ pub fn Settings(
                                 },
                                 placeholder: "https://api.moonshot.cn/v1",
                             }
+                            if let Some(hint) = validate_base_url(&editing_profile.read().base_url) {
+                                div { class: "field-hint", "{hint}" }
+                            }
                         }
                         div { class: "form-group",
                             label { "Model ID (模型名)" }
@@ -172,6 +482,9 @@ pub fn Settings(
                                 },
                                 placeholder: "moonshot-v1-8k",
                             }
+                            if let Some(hint) = validate_model_id(&editing_profile.read().model_id) {
+                                div { class: "field-hint", "{hint}" }
+                            }
                         }
                         div { class: "form-group",
                             label { "API Key" }
@@ -185,12 +498,179 @@ pub fn Settings(
                                 },
                                 placeholder: "sk-...",
                             }
+                            if let Some(hint) = validate_api_key(&editing_profile.read().api_key) {
+                                div { class: "field-hint", "{hint}" }
+                            }
+                        }
+                        div { class: "form-group",
+                            div {
+                                class: "add-model-btn",
+                                onclick: move |_| run_test_connection(editing_profile.read().clone()),
+                                "测试连接"
+                            }
+                            {
+                                let current = editing_profile.read().id.clone();
+                                match test_results.read().get(&current) {
+                                    Some(ConnectionTestStatus::Testing) => rsx! {
+                                        div { class: "field-hint", "正在测试…" }
+                                    },
+                                    Some(ConnectionTestStatus::Success { latency_ms }) => rsx! {
+                                        div { class: "field-hint success", "✓ 连接成功，耗时 {latency_ms}ms" }
+                                    },
+                                    Some(ConnectionTestStatus::Failed(err)) => rsx! {
+                                        div { class: "field-hint", "✗ 连接失败: {err}" }
+                                    },
+                                    None => rsx! {},
+                                }
+                            }
                         }
                         div { style: "margin-top: 30px; font-size: 12px; color: #999; text-align: center;",
                             "配置会自动保存"
                         }
                     }
                 }
+
+                div { class: "settings-body", style: "border-top: 1px dashed #ddd; margin-top: 16px; padding-top: 16px;",
+                    div { class: "settings-sidebar",
+                        div { class: "sidebar-label", "导入字段映射" }
+                        {
+                            schemas
+                                .into_iter()
+                                .map(|schema| {
+                                    let s_id = schema.id.clone();
+                                    let id_for_click = schema.id.clone();
+                                    let id_for_del = schema.id.clone();
+                                    let is_active = Some(&s_id) == active_schema_id.as_ref();
+                                    rsx! {
+                                        div {
+                                            key: "{s_id}",
+                                            class: if is_active { "model-item active" } else { "model-item" },
+                                            onclick: move |_| select_schema(id_for_click.clone()),
+                                            div { style: "display: flex; justify-content: space-between; align-items: center;",
+                                                div { class: "model-name", "{schema.name}" }
+                                                div {
+                                                    class: "del-btn",
+                                                    style: "color: #999; font-size: 12px; padding: 4px;",
+                                                    onclick: move |evt| {
+                                                        evt.stop_propagation();
+                                                        delete_schema(id_for_del.clone());
+                                                    },
+                                                    "✕"
+                                                }
+                                            }
+                                            div { class: "model-desc", "{schema.fields.len()} 个字段" }
+                                        }
+                                    }
+                                })
+                        }
+                        div { class: "add-model-btn", onclick: move |_| add_schema(), "+ 新增导入模板" }
+                    }
+
+                    div { class: "settings-content",
+                        if let Some(schema) = active_schema {
+                            div { class: "form-header", "编辑字段映射" }
+                            div { class: "form-group",
+                                label { "模板名称" }
+                                input {
+                                    class: "comic-input",
+                                    value: "{schema.name}",
+                                    oninput: move |evt| {
+                                        let name = evt.value();
+                                        update_active_schema(Box::new(move |s| s.name = name.clone()));
+                                    },
+                                }
+                            }
+                            for (idx , field) in schema.fields.iter().enumerate() {
+                                div {
+                                    key: "{idx}",
+                                    class: "form-group",
+                                    style: "display: flex; gap: 6px; align-items: center; border-bottom: 1px solid #eee; padding-bottom: 8px;",
+                                    input {
+                                        class: "comic-input",
+                                        style: "flex: 1;",
+                                        placeholder: "原始表头，比如 姓名",
+                                        value: "{field.header}",
+                                        oninput: move |evt| {
+                                            let v = evt.value();
+                                            update_active_schema(Box::new(move |s| {
+                                                if let Some(f) = s.fields.get_mut(idx) {
+                                                    f.header = v.clone();
+                                                }
+                                            }));
+                                        },
+                                    }
+                                    input {
+                                        class: "comic-input",
+                                        style: "flex: 1;",
+                                        placeholder: "映射 key，比如 name",
+                                        value: "{field.key}",
+                                        oninput: move |evt| {
+                                            let v = evt.value();
+                                            update_active_schema(Box::new(move |s| {
+                                                if let Some(f) = s.fields.get_mut(idx) {
+                                                    f.key = v.clone();
+                                                }
+                                            }));
+                                        },
+                                    }
+                                    select {
+                                        class: "comic-input",
+                                        onchange: move |evt| {
+                                            let kind = evt.value();
+                                            update_active_schema(Box::new(move |s| {
+                                                if let Some(f) = s.fields.get_mut(idx) {
+                                                    f.field_type = match kind.as_str() {
+                                                        "number" => FieldType::Number,
+                                                        "date" => FieldType::Date,
+                                                        "select" => FieldType::Select { options: Vec::new() },
+                                                        _ => FieldType::Text,
+                                                    };
+                                                }
+                                            }));
+                                        },
+                                        option { value: "text", selected: matches!(field.field_type, FieldType::Text), "文本" }
+                                        option { value: "number", selected: matches!(field.field_type, FieldType::Number), "数字" }
+                                        option { value: "date", selected: matches!(field.field_type, FieldType::Date), "日期" }
+                                        option { value: "select", selected: matches!(field.field_type, FieldType::Select { .. }), "下拉选项" }
+                                    }
+                                    if let FieldType::Select { options } = &field.field_type {
+                                        input {
+                                            class: "comic-input",
+                                            style: "flex: 1;",
+                                            placeholder: "允许值，逗号分隔",
+                                            value: "{options.join(\",\")}",
+                                            oninput: move |evt| {
+                                                let opts: Vec<String> = evt
+                                                    .value()
+                                                    .split(',')
+                                                    .map(|s| s.trim().to_string())
+                                                    .filter(|s| !s.is_empty())
+                                                    .collect();
+                                                update_active_schema(Box::new(move |s| {
+                                                    if let Some(f) = s.fields.get_mut(idx) {
+                                                        f.field_type = FieldType::Select { options: opts.clone() };
+                                                    }
+                                                }));
+                                            },
+                                        }
+                                    }
+                                    div {
+                                        class: "del-btn",
+                                        style: "color: #999; font-size: 12px; padding: 4px; cursor: pointer;",
+                                        onclick: move |_| remove_field(idx),
+                                        "✕"
+                                    }
+                                }
+                            }
+                            div { style: "display: flex; gap: 8px; margin-top: 10px;",
+                                button { class: "add-model-btn", onclick: move |_| add_field(), "+ 添加字段" }
+                                button { class: "add-model-btn", onclick: download_template, "⬇ 下载模板" }
+                            }
+                        } else {
+                            div { style: "color: #999; font-size: 13px;", "选一份导入模板开始编辑字段映射，或者新增一份" }
+                        }
+                    }
+                }
             }
         }
     }
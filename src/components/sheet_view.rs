@@ -0,0 +1,500 @@
+use crate::models::{CellRange, SheetEdit, SheetGrid};
+use crate::services::sheet_history::{self, EditHistory};
+use dioxus::document::eval;
+use dioxus::prelude::*;
+
+/// 把 `services::python::read_sheet_grid` 解析出来的整张工作表渲染成可滚动、可缩放的网格
+///
+/// 表头行和首列用 `position: sticky` 冻结，横向/纵向滚动时始终可见。支持单击
+/// 选中单个格子、按住拖拽框选一块区域，选区通过 `selected_range` 这个 `Signal`
+/// 透传给父组件（`main.rs`），再转手给 `InputArea`，这样用户说"分析选中区域"
+/// 时能把具体的单元格范围带进发给 AI 的 prompt 里。
+///
+/// # 编辑 + 撤销/重做
+///
+/// 双击格子进入编辑态，回车提交、Esc 放弃；每一次提交都生成一条
+/// [`SheetEdit`] 推进 [`EditHistory`] 的撤销栈，`Ctrl+Z`/`Ctrl+Y`（或工具栏按钮）
+/// 调用 `EditHistory::undo`/`redo` 把对应的前/后快照写回 `sheet_grid`，不重新
+/// 克隆整张表。`sheet_grid` 现在以 `Signal` 形式传入，正是为了让这里能直接改。
+///
+/// # 全表搜索
+///
+/// 工具栏的搜索框调用 `services::sheet_history::search_sheet` 在当前加载的这
+/// 张表里找子串/数字命中，"上一个"/"下一个" 把 `selected_range` 挪到对应命中
+/// 并滚动进视口，方便在导出前确认 Agent 改对了地方。
+///
+/// 深色模式跟着 `main.rs` 写在 <html data-theme="..."> 上的属性走（`AppConfig.theme`
+/// 加系统外观算出来的那一个，参见 `App` 里的主题 `use_effect`），不再单独查
+/// `prefers-color-scheme`——这样用户在设置里手动选的 Light/Dark 也能覆盖这张表，
+/// 和 `DockCapsule`/`Settings` 走的是同一套主题子系统。
+#[component]
+pub fn SheetView(
+    sheet_grid: Signal<Option<SheetGrid>>,
+    selected_range: Signal<Option<CellRange>>,
+) -> Element {
+    let mut zoom = use_signal(|| 1.0_f64);
+    let mut anchor = use_signal(|| None::<(usize, usize)>);
+    let mut is_selecting = use_signal(|| false);
+    let mut history = use_signal(EditHistory::new);
+    let mut editing_cell = use_signal(|| None::<(usize, usize)>);
+    let mut edit_buffer = use_signal(String::new);
+    let mut search_query = use_signal(String::new);
+    let mut search_hits = use_signal(Vec::<(usize, usize)>::new);
+    let mut search_cursor = use_signal(|| 0usize);
+
+    let Some(grid) = sheet_grid() else {
+        return rsx! {};
+    };
+
+    let font_size = (13.0 * zoom()).round();
+
+    let mut begin_select = move |row: usize, col: usize| {
+        anchor.set(Some((row, col)));
+        is_selecting.set(true);
+        selected_range.set(Some(CellRange::single(row, col)));
+    };
+    let extend_select = move |row: usize, col: usize| {
+        if is_selecting() {
+            if let Some(start) = anchor() {
+                selected_range.set(Some(CellRange::normalized(start, (row, col))));
+            }
+        }
+    };
+    let end_select = move |_| is_selecting.set(false);
+
+    let is_selected = move |row: usize, col: usize| {
+        selected_range().is_some_and(|r| {
+            row >= r.row_start && row <= r.row_end && col >= r.col_start && col <= r.col_end
+        })
+    };
+
+    // 双击格子进入编辑态
+    let mut start_edit = move |row: usize, col: usize| {
+        let current = sheet_grid
+            .read()
+            .as_ref()
+            .and_then(|g| g.rows.get(row).and_then(|r| r.get(col).cloned()))
+            .flatten();
+        edit_buffer.set(current.unwrap_or_default());
+        editing_cell.set(Some((row, col)));
+    };
+
+    // 回车提交编辑：生成一条 CellEdit，写回 `sheet_grid`，推进撤销栈
+    let mut commit_edit = move || {
+        let Some((row, col)) = editing_cell() else {
+            return;
+        };
+        editing_cell.set(None);
+        let text = edit_buffer();
+        let after = if text.is_empty() { None } else { Some(text) };
+
+        let mut grid_guard = sheet_grid.write();
+        let Some(grid) = grid_guard.as_mut() else {
+            return;
+        };
+        let before = grid
+            .rows
+            .get(row)
+            .and_then(|r| r.get(col).cloned())
+            .flatten();
+        if before == after {
+            return;
+        }
+        history.write().apply_and_push(
+            grid,
+            SheetEdit::CellEdit {
+                row,
+                col,
+                before,
+                after,
+            },
+        );
+    };
+
+    // 在 `selected_range` 锚点处插入/删除整行整列，或者合并/取消合并选区——
+    // 都走同一个 `apply_and_push` 入口，跟编辑格子共用撤销栈
+    let mut insert_row_above = move |_| {
+        let Some(range) = selected_range() else {
+            return;
+        };
+        let mut grid_guard = sheet_grid.write();
+        let Some(grid) = grid_guard.as_mut() else {
+            return;
+        };
+        history.write().apply_and_push(
+            grid,
+            SheetEdit::RowInsert {
+                at: range.row_start,
+            },
+        );
+    };
+    let mut delete_row = move |_| {
+        let Some(range) = selected_range() else {
+            return;
+        };
+        let mut grid_guard = sheet_grid.write();
+        let Some(grid) = grid_guard.as_mut() else {
+            return;
+        };
+        let Some(cells) = grid.rows.get(range.row_start).cloned() else {
+            return;
+        };
+        history.write().apply_and_push(
+            grid,
+            SheetEdit::RowDelete {
+                at: range.row_start,
+                cells,
+            },
+        );
+    };
+    let mut insert_col_left = move |_| {
+        let Some(range) = selected_range() else {
+            return;
+        };
+        let mut grid_guard = sheet_grid.write();
+        let Some(grid) = grid_guard.as_mut() else {
+            return;
+        };
+        history.write().apply_and_push(
+            grid,
+            SheetEdit::ColInsert {
+                at: range.col_start,
+                header: "新列".into(),
+            },
+        );
+    };
+    let mut delete_col = move |_| {
+        let Some(range) = selected_range() else {
+            return;
+        };
+        let mut grid_guard = sheet_grid.write();
+        let Some(grid) = grid_guard.as_mut() else {
+            return;
+        };
+        let Some(header) = grid.headers.get(range.col_start).cloned() else {
+            return;
+        };
+        let cells: Vec<Option<String>> = grid
+            .rows
+            .iter()
+            .map(|r| r.get(range.col_start).cloned().flatten())
+            .collect();
+        history.write().apply_and_push(
+            grid,
+            SheetEdit::ColDelete {
+                at: range.col_start,
+                header,
+                cells,
+            },
+        );
+    };
+    let mut toggle_merge = move |_| {
+        let Some(range) = selected_range() else {
+            return;
+        };
+        if range.is_single_cell() {
+            return;
+        }
+        let mut grid_guard = sheet_grid.write();
+        let Some(grid) = grid_guard.as_mut() else {
+            return;
+        };
+        let already_merged = grid.merges.contains(&range);
+        let edit = if already_merged {
+            SheetEdit::Unmerge { range }
+        } else {
+            SheetEdit::Merge { range }
+        };
+        history.write().apply_and_push(grid, edit);
+    };
+
+    // Ctrl+V：把剪贴板文本（Excel/表格复制出来的 Tab/换行分隔文本）粘到选区左上角，
+    // 按粘贴内容的形状扩出一个矩形，生成一条 RangePaste
+    let paste_clipboard_text = move |text: String| {
+        let Some(range) = selected_range() else {
+            return;
+        };
+        let paste_rows: Vec<Vec<Option<String>>> = text
+            .lines()
+            .map(|line| {
+                line.split('\t')
+                    .map(|cell| {
+                        if cell.is_empty() {
+                            None
+                        } else {
+                            Some(cell.to_string())
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        if paste_rows.is_empty() {
+            return;
+        }
+        let width = paste_rows.iter().map(|r| r.len()).max().unwrap_or(1).max(1);
+
+        let mut grid_guard = sheet_grid.write();
+        let Some(grid) = grid_guard.as_mut() else {
+            return;
+        };
+        let row_end =
+            (range.row_start + paste_rows.len() - 1).min(grid.rows.len().saturating_sub(1));
+        let col_end = (range.col_start + width - 1).min(grid.headers.len().saturating_sub(1));
+        let target = CellRange {
+            row_start: range.row_start,
+            row_end,
+            col_start: range.col_start,
+            col_end,
+        };
+
+        let before: Vec<Vec<Option<String>>> = (target.row_start..=target.row_end)
+            .map(|r| {
+                (target.col_start..=target.col_end)
+                    .map(|c| grid.rows[r][c].clone())
+                    .collect()
+            })
+            .collect();
+        let after: Vec<Vec<Option<String>>> = (target.row_start..=target.row_end)
+            .map(|r| {
+                let src_row = &paste_rows[r - target.row_start];
+                (target.col_start..=target.col_end)
+                    .map(|c| src_row.get(c - target.col_start).cloned().flatten())
+                    .collect()
+            })
+            .collect();
+
+        history.write().apply_and_push(
+            grid,
+            SheetEdit::RangePaste {
+                range: target,
+                before,
+                after,
+            },
+        );
+        selected_range.set(Some(target));
+    };
+
+    let mut run_undo = move || {
+        let mut grid_guard = sheet_grid.write();
+        if let Some(grid) = grid_guard.as_mut() {
+            history.write().undo(grid);
+        }
+    };
+    let mut run_redo = move || {
+        let mut grid_guard = sheet_grid.write();
+        if let Some(grid) = grid_guard.as_mut() {
+            history.write().redo(grid);
+        }
+    };
+
+    let mut jump_to_hit = move |index: usize| {
+        let hits = search_hits();
+        let Some(&(row, col)) = hits.get(index) else {
+            return;
+        };
+        selected_range.set(Some(CellRange::single(row, col)));
+        let _ = eval(&format!(
+            r#"setTimeout(() => {{
+                const el = document.getElementById('sheet-cell-{row}-{col}');
+                if (el) el.scrollIntoView({{ block: 'center', inline: 'center' }});
+            }}, 0);"#
+        ));
+    };
+
+    let mut run_search = move |query: String| {
+        search_query.set(query.clone());
+        let hits = sheet_grid
+            .read()
+            .as_ref()
+            .map(|g| sheet_history::search_sheet(g, &query))
+            .unwrap_or_default();
+        search_hits.set(hits);
+        search_cursor.set(0);
+        jump_to_hit(0);
+    };
+
+    let go_next_hit = move |_| {
+        let hits = search_hits();
+        if hits.is_empty() {
+            return;
+        }
+        let next = (search_cursor() + 1) % hits.len();
+        search_cursor.set(next);
+        jump_to_hit(next);
+    };
+    let go_prev_hit = move |_| {
+        let hits = search_hits();
+        if hits.is_empty() {
+            return;
+        }
+        let prev = (search_cursor() + hits.len() - 1) % hits.len();
+        search_cursor.set(prev);
+        jump_to_hit(prev);
+    };
+
+    let is_hit = move |row: usize, col: usize| search_hits().contains(&(row, col));
+
+    rsx! {
+        document::Style { r#"
+            .sheet-view {{ --sheet-header-bg: #f3f3f3; --sheet-border: #ddd; --sheet-selected-bg: #cfe8ff; --sheet-hit-bg: #fff3b0; }}
+            [data-theme="dark"] .sheet-view {{ --sheet-header-bg: #2a2a2a; --sheet-border: #444; --sheet-selected-bg: #2d4a66; --sheet-hit-bg: #5a4b00; background: #1e1e1e; color: #ddd; }}
+        "# }
+
+        div {
+            class: "sheet-view",
+            style: "display: flex; flex-direction: column; height: 100%; min-height: 0;",
+            tabindex: "0",
+            onmouseup: end_select,
+            onmouseleave: end_select,
+            onkeydown: move |evt| {
+                let ctrl = evt.modifiers().contains(Modifiers::CONTROL) || evt.modifiers().contains(Modifiers::META);
+                if !ctrl {
+                    return;
+                }
+                match evt.key() {
+                    Key::Character(c) if c.eq_ignore_ascii_case("z") => {
+                        evt.prevent_default();
+                        run_undo();
+                    }
+                    Key::Character(c) if c.eq_ignore_ascii_case("y") => {
+                        evt.prevent_default();
+                        run_redo();
+                    }
+                    Key::Character(c) if c.eq_ignore_ascii_case("v") => {
+                        evt.prevent_default();
+                        spawn(async move {
+                            let mut clip = eval(
+                                r#"try {
+                                    const t = await navigator.clipboard.readText();
+                                    dioxus.send(t);
+                                } catch (e) {
+                                    dioxus.send("");
+                                }"#,
+                            );
+                            if let Ok(text) = clip.recv::<String>().await {
+                                if !text.is_empty() {
+                                    paste_clipboard_text(text);
+                                }
+                            }
+                        });
+                    }
+                    _ => {}
+                }
+            },
+
+            div {
+                class: "sheet-toolbar",
+                style: "display: flex; align-items: center; gap: 8px; padding: 6px 10px; font-size: 12px; color: #888; flex-wrap: wrap;",
+                span {
+                    if let Some(range) = selected_range() {
+                        "已选中 {range.describe()}"
+                    } else {
+                        "点击或拖拽单元格可框选区域，双击编辑"
+                    }
+                }
+                button {
+                    disabled: !history.read().can_undo(),
+                    title: "撤销 (Ctrl+Z)",
+                    onclick: move |_| run_undo(),
+                    "↶ 撤销"
+                }
+                button {
+                    disabled: !history.read().can_redo(),
+                    title: "重做 (Ctrl+Y)",
+                    onclick: move |_| run_redo(),
+                    "↷ 重做"
+                }
+                button { disabled: selected_range().is_none(), onclick: insert_row_above, "插入行" }
+                button { disabled: selected_range().is_none(), onclick: delete_row, "删除行" }
+                button { disabled: selected_range().is_none(), onclick: insert_col_left, "插入列" }
+                button { disabled: selected_range().is_none(), onclick: delete_col, "删除列" }
+                button {
+                    disabled: selected_range().map_or(true, |r| r.is_single_cell()),
+                    onclick: toggle_merge,
+                    "合并/取消合并"
+                }
+                input {
+                    r#type: "text",
+                    placeholder: "搜索...",
+                    style: "width: 100px;",
+                    value: "{search_query}",
+                    oninput: move |evt| run_search(evt.value()),
+                }
+                if !search_hits().is_empty() {
+                    span { "{search_cursor() + 1}/{search_hits().len()}" }
+                    button { onclick: go_prev_hit, "‹" }
+                    button { onclick: go_next_hit, "›" }
+                } else if !search_query().is_empty() {
+                    span { "无命中" }
+                }
+                div { style: "flex: 1;" }
+                button { onclick: move |_| zoom.set((zoom() - 0.1).max(0.5)), "－" }
+                span { "{(zoom() * 100.0) as i32}%" }
+                button { onclick: move |_| zoom.set((zoom() + 0.1).min(2.0)), "＋" }
+            }
+
+            div {
+                class: "sheet-grid-scroll",
+                style: "flex: 1; overflow: auto; font-size: {font_size}px;",
+                table {
+                    style: "border-collapse: collapse; white-space: nowrap;",
+                    thead {
+                        tr {
+                            th {
+                                style: "position: sticky; top: 0; left: 0; z-index: 3; background: var(--sheet-header-bg); border: 1px solid var(--sheet-border); min-width: 36px;",
+                                ""
+                            }
+                            for (col , header) in grid.headers.iter().enumerate() {
+                                th {
+                                    key: "{col}",
+                                    style: "position: sticky; top: 0; z-index: 2; background: var(--sheet-header-bg); border: 1px solid var(--sheet-border); padding: 4px 8px;",
+                                    "{header}"
+                                }
+                            }
+                        }
+                    }
+                    tbody {
+                        for (row , cells) in grid.rows.iter().enumerate() {
+                            tr {
+                                key: "{row}",
+                                th {
+                                    style: "position: sticky; left: 0; z-index: 1; background: var(--sheet-header-bg); border: 1px solid var(--sheet-border); padding: 4px 8px;",
+                                    "{row + 1}"
+                                }
+                                for (col , cell) in cells.iter().enumerate() {
+                                    td {
+                                        key: "{col}",
+                                        id: "sheet-cell-{row}-{col}",
+                                        style: "border: 1px solid var(--sheet-border); padding: 4px 8px; background: {if is_selected(row, col) { \"var(--sheet-selected-bg)\" } else if is_hit(row, col) { \"var(--sheet-hit-bg)\" } else { \"transparent\" }};",
+                                        onmousedown: move |_| begin_select(row, col),
+                                        onmouseenter: move |_| extend_select(row, col),
+                                        ondoubleclick: move |_| start_edit(row, col),
+                                        if editing_cell() == Some((row, col)) {
+                                            input {
+                                                r#type: "text",
+                                                style: "width: 100%; font-size: inherit; border: none; outline: none;",
+                                                value: "{edit_buffer}",
+                                                autofocus: true,
+                                                oninput: move |evt| edit_buffer.set(evt.value()),
+                                                onkeydown: move |evt| {
+                                                    match evt.key() {
+                                                        Key::Enter => commit_edit(),
+                                                        Key::Escape => editing_cell.set(None),
+                                                        _ => {}
+                                                    }
+                                                },
+                                                onblur: move |_| commit_edit(),
+                                            }
+                                        } else {
+                                            "{cell.clone().unwrap_or_default()}"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
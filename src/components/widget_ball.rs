@@ -1,6 +1,6 @@
 use dioxus::{desktop::use_window, html::HasFileData, prelude::*};
 
-use crate::models::{ActionStatus, ChatMessage, WindowMode};
+use crate::models::{ChatMessage, WindowMode};
 
 #[component]
 pub fn WidgetBall(
@@ -58,17 +58,11 @@ pub fn WidgetBall(
 
                         // Send Message
                         let new_id = messages.read().len();
-                        messages
-                            .write()
-                            .push(ChatMessage {
-                                id: new_id,
-                                text: format!("📂 已通过悬浮球加载: {}", file_name),
-                                is_user: false,
-                                table: None,
-                                temp_id: None,
-                                status: ActionStatus::None,
-                                image: None,
-                            });
+                        messages.write().push(ChatMessage::new(
+                            new_id,
+                            format!("📂 已通过悬浮球加载: {}", file_name),
+                            false,
+                        ));
                         window_mode.set(WindowMode::Main);
                     }
                 },
@@ -6,22 +6,30 @@ mod services;
 
 use std::path::Path;
 use std::time::Duration;
+use tokio::task;
 
 use dioxus::desktop::tao::platform::windows::WindowBuilderExtWindows;
+use dioxus::desktop::tao::window::Theme as OsTheme;
 use dioxus::desktop::trayicon::{Icon, MouseButton, TrayIconBuilder, TrayIconEvent};
 use dioxus::desktop::wry::dpi::PhysicalPosition;
 use dioxus::desktop::{
     use_tray_icon_event_handler, Config, LogicalPosition, LogicalSize, WindowBuilder,
 };
+use dioxus::document::eval;
 use dioxus::prelude::*;
 use futures_util::StreamExt;
 
 use crate::components::dock_capsule::DockCapsule;
-use crate::models::{ActionStatus, WindowMode};
+use crate::components::global_hotkeys::GlobalHotkeys;
+use crate::models::{ActionStatus, AppState, Theme, WindowMode};
 use crate::services::config::load_config;
 use crate::services::python::{create_batch_backups, run_batch_hot_undo, run_python_code};
-use components::{chat_view::ChatView, input_area::InputArea, settings::Settings};
+use components::{
+    chat_view::ChatView, conversation_list::ConversationList, input_area::InputArea,
+    settings::Settings, sheet_view::SheetView,
+};
 use models::ChatMessage;
+use uuid::Uuid;
 
 #[cfg(target_os = "windows")]
 use windows_sys::Win32::Foundation::RECT;
@@ -382,14 +390,176 @@ fn App() -> Element {
     });
 
     // --- 状态定义 ---
-    let mut messages =
-        use_signal(|| vec![ChatMessage::new(0, "👋 嗨！把 Excel 拖进来开始吧。", false)]);
+    // 多会话状态：每个 Conversation 拥有独立的消息历史。
+    // 启动时优先从磁盘索引恢复会话列表（消息本体按需懒加载，不在这里一次性读入）。
+    let mut app_state = use_signal(|| {
+        let persisted = services::history::load_index();
+        if persisted.is_empty() {
+            AppState::new()
+        } else {
+            let active_id = persisted.last().map(|c| c.id);
+            AppState {
+                conversations: persisted,
+                active_id,
+            }
+        }
+    });
+    // `messages` 始终镜像"当前激活会话"已加载的那一页消息，ChatView/InputArea 照常只认这一个
+    // Signal；切换/翻页时从 services::history 按需读取，而不是一次性把全部历史放进内存。
+    let mut messages = use_signal(|| {
+        app_state
+            .read()
+            .active_id
+            .map(|id| services::history::load_latest(id, services::history::PAGE_SIZE).0)
+            .unwrap_or_default()
+    });
+    // 当前会话是否还有更早的历史可以向上翻页加载
+    let mut has_more_history = use_signal(|| {
+        app_state
+            .read()
+            .active_id
+            .map(|id| services::history::load_latest(id, services::history::PAGE_SIZE).1)
+            .unwrap_or(false)
+    });
     let config = use_signal(|| load_config());
+
+    // 操作系统深色/浅色切换没有现成的事件可以订阅（这套 use_window 目前只接了托盘
+    // 图标的事件总线），"跟随系统"时只能轮询 `theme()` 的返回值
+    let mut os_theme_dark = use_signal(|| window.theme() == OsTheme::Dark);
+    let window_theme_poll = window.clone();
+    use_effect(move || {
+        let window_poll = window_theme_poll.clone();
+        spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                let is_dark = window_poll.theme() == OsTheme::Dark;
+                if is_dark != os_theme_dark() {
+                    os_theme_dark.set(is_dark);
+                }
+            }
+        });
+    });
+
+    // 主题：配置里的偏好 + （跟随系统时）操作系统当前外观，算出最终要渲染的明暗，
+    // 写到 <html data-theme="..."> 上，CSS 用 `[data-theme="dark"]` 选择器覆盖
+    // 浅色样式即可，`DockCapsule` 和 `Settings` 不需要各自再判断一遍
+    use_effect(move || {
+        let is_dark = match config.read().theme {
+            Theme::Light => false,
+            Theme::Dark => true,
+            Theme::FollowSystem => os_theme_dark(),
+        };
+        let value = if is_dark { "dark" } else { "light" };
+        let _ = eval(&format!(
+            "document.documentElement.setAttribute('data-theme', '{value}');"
+        ));
+    });
+
     // 多文件状态
     let mut active_files = use_signal(|| Vec::<String>::new());
-    let is_loading = use_signal(|| false);
-    // 错误修复信号
+    // 欢迎页网格预览：解析出来的工作表数据，以及用户在里面框选的单元格区域
+    let mut sheet_grid = use_signal(|| None::<models::SheetGrid>);
+    let selected_range = use_signal(|| None::<models::CellRange>);
+    // 错误修复信号：自动执行的代码报错走这里，文件打不开（格式不支持/已损坏）也
+    // 复用同一条通道，UI 只需要盯着这一个信号就能知道"有错误需要用户/Agent 关注"
     let mut error_fix_signal = use_signal(|| None::<String>);
+
+    // 还没开始聊天、且工作区里有文件时，读取第一个文件的内容铺成网格预览；
+    // 换了文件或者清空工作区都要跟着刷新
+    use_effect(move || {
+        let current_file = active_files.read().first().cloned();
+        match current_file {
+            Some(path) => {
+                spawn(async move {
+                    match services::python::read_sheet_grid(&path, None).await {
+                        Ok(grid) => sheet_grid.set(Some(grid)),
+                        Err(e) => {
+                            let format = services::file_format::detect(&path);
+                            error_fix_signal.set(Some(format!(
+                                "⚠️ 无法解析文件（识别为 {}）: {}",
+                                format.badge(),
+                                e
+                            )));
+                            sheet_grid.set(None);
+                        }
+                    }
+                });
+            }
+            None => sheet_grid.set(None),
+        }
+    });
+
+    // 每当消息变化时同步回当前激活会话、落盘，并在首条用户消息出现时自动生成标题
+    use_effect(move || {
+        let snapshot = messages.read().clone();
+        let current_file = active_files.read().first().cloned();
+        let active_id = app_state.read().active_id;
+        let mut state = app_state.write();
+        if let Some(conv) = state.active_mut() {
+            conv.messages = snapshot.clone();
+            if let Some(path) = current_file {
+                conv.last_file_path = path;
+            }
+            conv.touch();
+            if conv.title == "新会话" {
+                if let Some(first_user_msg) = snapshot.iter().find(|m| m.is_user) {
+                    conv.auto_title_from(&first_user_msg.text);
+                }
+            }
+        }
+        let snapshot_index = state.conversations.clone();
+        drop(state);
+        services::history::save_index(&snapshot_index);
+        if let Some(id) = active_id {
+            services::history::save_messages(id, &snapshot);
+        }
+    });
+
+    // 新建会话
+    let on_new_conversation = move |_: ()| {
+        app_state.write().new_conversation();
+        messages.set(Vec::new());
+        has_more_history.set(false);
+    };
+
+    // 切换会话：把当前缓冲区留在 app_state 里，从磁盘按页加载目标会话最新的一页
+    let on_select_conversation = move |target_id: Uuid| {
+        app_state.write().active_id = Some(target_id);
+        let (loaded, more) =
+            services::history::load_latest(target_id, services::history::PAGE_SIZE);
+        messages.set(loaded);
+        has_more_history.set(more);
+    };
+
+    // 删除会话：若删的是当前激活会话，自动切到新的激活会话并重载缓冲区
+    let on_delete_conversation = move |target_id: Uuid| {
+        app_state.write().delete(target_id);
+        if app_state.read().conversations.is_empty() {
+            app_state.write().new_conversation();
+        }
+        let active_id = app_state.read().active_id;
+        let (loaded, more) = active_id
+            .map(|id| services::history::load_latest(id, services::history::PAGE_SIZE))
+            .unwrap_or_default();
+        messages.set(loaded);
+        has_more_history.set(more);
+    };
+
+    // 用户把聊天滚动到顶部附近时，向磁盘请求更早的一页，并保持滚动位置不跳动
+    let on_load_more_history = move |_: ()| {
+        let active_id = app_state.read().active_id;
+        let Some(id) = active_id else { return };
+        let already_loaded = messages.read().len();
+        let (older, more) =
+            services::history::load_older_page(id, already_loaded, services::history::PAGE_SIZE);
+        has_more_history.set(more);
+        if !older.is_empty() {
+            let mut combined = older;
+            combined.extend(messages.read().clone());
+            messages.set(combined);
+        }
+    };
+    let is_loading = use_signal(|| false);
     let mut retry_count = use_signal(|| 0);
     const MAX_RETRIES: i32 = 3;
 
@@ -398,20 +568,54 @@ fn App() -> Element {
         // 🔥 修复：现在 rx.next() 可以工作了，因为引入了 StreamExt
         while let Some(path) = rx.next().await {
             println!("👉 Coroutine 收到文件: {}", path); // 打印日志
-            let mut current = active_files.write();
-            if !current.contains(&path) {
-                let new_id = messages.read().len();
-                let file_name = Path::new(&path)
-                    .file_name()
-                    .unwrap_or_default()
-                    .to_string_lossy();
-                messages.write().push(ChatMessage::new(
-                    new_id,
-                    &format!("📄 收到文件: {}", file_name),
-                    false,
-                ));
-                current.push(path);
-                window_mode.set(WindowMode::Main);
+            let is_new = {
+                let mut current = active_files.write();
+                if current.contains(&path) {
+                    false
+                } else {
+                    let new_id = messages.read().len();
+                    let file_name = Path::new(&path)
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy();
+                    messages.write().push(ChatMessage::new(
+                        new_id,
+                        &format!("📄 收到文件: {}", file_name),
+                        false,
+                    ));
+                    current.push(path.clone());
+                    window_mode.set(WindowMode::Main);
+                    true
+                }
+            };
+
+            // 如果设置里选了一份激活的导入 schema，顺手把这份文件按 schema 转成
+            // 干净的类型化记录，而不是甩给 Agent 一整张没有列类型概念的原始表
+            if is_new {
+                if let Some(schema) = config.read().active_import_schema().cloned() {
+                    match services::python::read_sheet_grid(&path, None).await {
+                        Ok(grid) => {
+                            let (records, skipped) =
+                                services::import_schema::import_rows(&grid, &schema);
+                            let msg_id = messages.read().len();
+                            messages.write().push(ChatMessage::new(
+                                msg_id,
+                                services::import_schema::summarize_import(
+                                    &schema, &records, skipped,
+                                ),
+                                false,
+                            ));
+                        }
+                        Err(e) => {
+                            let format = services::file_format::detect(&path);
+                            error_fix_signal.set(Some(format!(
+                                "⚠️ 无法按导入模板解析文件（识别为 {}）: {}",
+                                format.badge(),
+                                e
+                            )));
+                        }
+                    }
+                }
             }
         }
     });
@@ -421,7 +625,7 @@ fn App() -> Element {
         spawn(async move {
             // 使用 rfd 弹出原生选择框
             if let Some(path) = rfd::AsyncFileDialog::new()
-                .add_filter("Excel", &["xlsx", "xls", "xlsm"])
+                .add_filter("表格文件", &["xlsx", "xls", "xlsm", "csv", "ods"])
                 .pick_file()
                 .await
             {
@@ -468,37 +672,57 @@ fn App() -> Element {
                     println!("🛡️ 检测到已有备份，跳过本次备份，保留原始快照。");
                 }
 
-                // 4. 执行 AI 代码
-                let res = run_python_code(&code).await;
+                // 4. 执行 AI 代码：内核是同步阻塞协议，套一层 spawn_blocking，
+                // 再把回执的 JSON 解析成 PyExecResult（跟 `ai::exec_code_once` 是
+                // 同一套调用方式）
+                let active_file = current_files.first().cloned().unwrap_or_default();
+                let op_id = Uuid::new_v4().to_string();
+                let json_str =
+                    task::spawn_blocking(move || run_python_code(&active_file, &code, &op_id))
+                        .await
+                        .unwrap_or_else(|e| {
+                            format!(
+                                "{{\"status\":\"error\",\"message\":\"Python 执行线程崩溃: {}\"}}",
+                                e
+                            )
+                        });
+                let res: models::PyExecResult =
+                    serde_json::from_str(&json_str).unwrap_or_else(|e| models::PyExecResult {
+                        status: "error".into(),
+                        message: format!("内部结果解析失败: {}", e),
+                        preview: None,
+                        stdout: None,
+                        image: None,
+                    });
                 // 结果处理
                 let mut msgs = messages.write();
                 if let Some(msg) = msgs.get_mut(msg_id) {
-                    match res {
-                        Ok(out) => {
-                            msg.status = ActionStatus::Success;
-                            msg.text.push_str(&format!("\n\n✨ 结果:\n{}", out));
+                    if res.status != "error" {
+                        msg.status = ActionStatus::Success;
+                        msg.table = res.preview;
+                        msg.image = res.image;
+                        msg.text.push_str(&format!("\n\n✨ 结果:\n{}", res.message));
+                        retry_count.set(0);
+                    } else {
+                        let e = res.message;
+                        msg.status = ActionStatus::Error(e.clone());
+                        msg.text.push_str(&format!("\n\n❌ 错误:\n{}", e));
+                        let current_retries = *retry_count.read();
+                        if current_retries < MAX_RETRIES {
+                            retry_count += 1;
+                            msg.text.push_str(&format!(
+                                "\n\n🔄 自动修复中 (尝试 {}/{})...",
+                                current_retries + 1,
+                                MAX_RETRIES
+                            ));
+                            error_fix_signal.set(Some(e));
+                        } else {
+                            msg.text.push_str(&format!(
+                                "\n\n🛑 已达到最大重试次数 ({})，停止自动修复。",
+                                MAX_RETRIES
+                            ));
                             retry_count.set(0);
                         }
-                        Err(e) => {
-                            msg.status = ActionStatus::Error(e.clone());
-                            msg.text.push_str(&format!("\n\n❌ 错误:\n{}", e));
-                            let current_retries = *retry_count.read();
-                            if current_retries < MAX_RETRIES {
-                                retry_count += 1;
-                                msg.text.push_str(&format!(
-                                    "\n\n🔄 自动修复中 (尝试 {}/{})...",
-                                    current_retries + 1,
-                                    MAX_RETRIES
-                                ));
-                                error_fix_signal.set(Some(e));
-                            } else {
-                                msg.text.push_str(&format!(
-                                    "\n\n🛑 已达到最大重试次数 ({})，停止自动修复。",
-                                    MAX_RETRIES
-                                ));
-                                retry_count.set(0);
-                            }
-                        }
                     }
                 }
             });
@@ -560,6 +784,53 @@ fn App() -> Element {
         }
     };
 
+    // 导出单条消息的结果表：解析成一张 sheet，弹原生保存框写成 .xlsx
+    let on_export = move |msg_id: usize| {
+        let table_html = messages
+            .read()
+            .iter()
+            .find(|m| m.id == msg_id)
+            .and_then(|m| m.table.clone());
+        let Some(html) = table_html else {
+            return;
+        };
+
+        spawn(async move {
+            let Some(save_path) = rfd::AsyncFileDialog::new()
+                .set_file_name("结果.xlsx")
+                .add_filter("Excel", &["xlsx"])
+                .save_file()
+                .await
+            else {
+                return;
+            };
+
+            let Some(sheet) = services::export::parse_html_table(&html, "结果") else {
+                let err_id = messages.read().len();
+                messages.write().push(ChatMessage::new(
+                    err_id,
+                    "❌ 导出失败: 没能从这条消息里解析出表格",
+                    false,
+                ));
+                return;
+            };
+
+            let out_path = save_path.path().to_string_lossy().to_string();
+            let result = task::spawn_blocking(move || {
+                services::export::export_sheets_to_xlsx(&[sheet], &out_path)
+            })
+            .await
+            .unwrap_or_else(|e| Err(format!("导出线程崩溃: {}", e)));
+
+            let msg_id = messages.read().len();
+            let text = match result {
+                Ok(()) => format!("✅ 已导出到 {}", save_path.path().display()),
+                Err(e) => format!("❌ 导出失败: {}", e),
+            };
+            messages.write().push(ChatMessage::new(msg_id, text, false));
+        });
+    };
+
     let mut remove_file = move |path: String| {
         let mut files = active_files.write();
         files.retain(|f| f != &path);
@@ -588,18 +859,14 @@ fn App() -> Element {
             .unwrap_or_default()
             .to_string_lossy()
             .to_string();
-        // 根据扩展名给一点不同的视觉
-        let ext = Path::new(&p)
-            .extension()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string()
-            .to_uppercase();
+        // 格式徽章优先认魔数、认不出才看扩展名，免得用户把 .xls 改名成 .xlsx 之类的乌龙
+        // 在这里就露馅
+        let format_badge = services::file_format::detect(&p).badge();
 
         rsx! {
             div { class: "file-card", title: "{p}", // hover 显示全路径
                 div { class: "file-icon-box",
-                    div { class: "file-icon-text", "{ext}" } // 显示 XLSX / CSV
+                    div { class: "file-icon-text", "{format_badge}" } // 显示 XLSX / XLS / CSV / ODS
                 }
                 div { class: "file-info",
                     div { class: "file-name", "{name}" }
@@ -616,18 +883,37 @@ fn App() -> Element {
         }
     });
 
+    // 全局热键：绑定字符串变了就靠 key 重新挂载 GlobalHotkeys，让它用新组合键
+    // 重新注册一遍，参见该组件自己的文档注释
+    let hotkey_summon = config.read().hotkey_summon.clone();
+    let hotkey_dismiss = config.read().hotkey_dismiss.clone();
+    let window_hotkey_summon = window.clone();
+
     rsx! {
         document::Stylesheet { href: asset!("/assets/lib/atom-one-dark.min.css") }
         document::Stylesheet { href: asset!("/assets/main.css") }
         script { src: asset!("/assets/lib/highlight.min.js") }
         script { src: asset!("/assets/lib/python.min.js") }
 
+        GlobalHotkeys {
+            key: "{hotkey_summon}-{hotkey_dismiss}",
+            summon: hotkey_summon,
+            dismiss: hotkey_dismiss,
+            on_summon: move |_| {
+                window_hotkey_summon.set_visible(true);
+                window_hotkey_summon.set_focus();
+                change_mode(WindowMode::Main);
+            },
+            on_dismiss: move |_| change_mode(WindowMode::Widget),
+        }
+
         if window_mode() == WindowMode::Widget {
             DockCapsule {
                 window_mode,
                 messages,
                 last_file_path: use_signal(|| active_files.read().first().cloned().unwrap_or_default()),
                 on_switch_mode: change_mode, // 传入回调
+                config,
             }
         } else if window_mode() == WindowMode::Settings {
             div {
@@ -653,6 +939,12 @@ fn App() -> Element {
                 }
 
                 div { class: "app-container",
+                    ConversationList {
+                        state: app_state,
+                        on_new: on_new_conversation,
+                        on_select: on_select_conversation,
+                        on_delete: on_delete_conversation,
+                    }
                     // 3. 应用动态布局 Class
                     div { class: "{content_mode_class}",
                         if !active_files.read().is_empty() {
@@ -677,7 +969,17 @@ fn App() -> Element {
                                 on_confirm: on_manual_confirm,
                                 on_cancel,
                                 on_undo,
+                                on_export,
+                                has_more_history: has_more_history(),
+                                on_load_more: on_load_more_history,
+                                config,
                             }
+                        } else if sheet_grid.read().is_some() {
+                            // 🔥 5. 居中模式下，工作区已经有文件时，直接预览表格内容，
+                            // 而不是甩一句"拖入表格，开始分析"的空话——用户拖进来的
+                            // 文件到底长什么样，现在能当场看到、当场框选区域，还能直接
+                            // 编辑/撤销/搜索（见 `SheetView`），不用等 Agent 跑代码
+                            div { style: "flex: 1; min-height: 0; width: 100%;", SheetView { sheet_grid, selected_range } }
                         } else {
                             // 🔥 5. 居中模式下的欢迎语 (代替之前的 ChatView)
                             div { style: "text-align: center; margin-bottom: 30px; color: #666; animation: fadeIn 0.5s;",
@@ -697,6 +999,7 @@ fn App() -> Element {
                             error_fix_signal,
                             on_run_code: on_auto_run,
                             on_open_file: open_file_dialog,
+                            selected_range,
                         }
                     }
                 }
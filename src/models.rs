@@ -19,6 +19,8 @@ pub enum ActionStatus {
     None,
     /// 等待 AI 响应中
     Loading,
+    /// AI 正在逐字(token)流式输出回复，`ChatMessage.text` 会持续增长
+    Streaming,
     /// 🔥 关键状态：AI 生成了代码，等待用户点击“执行”
     WaitingConfirmation,
     /// Python 代码正在后台执行
@@ -41,11 +43,20 @@ pub struct ChatMessage {
     pub is_user: bool,
     /// 可选：如果是数据消息，包含 HTML 表格
     pub table: Option<String>,
+    /// 可选：富文本输入框组合出的净化后 HTML（加粗/行内代码/列表/表格片段），
+    /// 聊天气泡优先渲染这个而不是再拿 `text` 走一遍 Markdown 解析；`text` 本身
+    /// 仍然保留去除了格式标记的纯文本，发给模型的是 `text`，不是这份 HTML
+    #[serde(default)]
+    pub rich_html: Option<String>,
     pub temp_id: Option<String>,
     /// 当前消息的状态
     pub status: ActionStatus,
-    /// 可选：图片路径
+    /// 可选：单张图片，内核回执里是 `data:image/png;base64,...` 形式的 data URL（兼容字段，单图场景；新代码请优先使用 `images`）
     pub image: Option<String>,
+    /// 可选：一次运行产出的多张图片（例如一次"思考过程"里跑出好几张图表），
+    /// 缩略图条会按顺序展示，点击任意一张都打开同一个 Lightbox 并可在其中切换。
+    #[serde(default)]
+    pub images: Vec<String>,
 
     /// 待执行的 Python 代码 (仅当 status == WaitingConfirmation 时有效)
     pub pending_code: Option<String>,
@@ -67,9 +78,11 @@ impl ChatMessage {
             text: text.into(),
             is_user,
             table: None,
+            rich_html: None,
             temp_id: None,
             status: ActionStatus::None,
             image: None,
+            images: Vec::new(),
             pending_code: None,
             backup_paths: None,
         }
@@ -82,13 +95,407 @@ impl ChatMessage {
             text: "正在思考...".into(),
             is_user: false,
             table: None,
+            rich_html: None,
             temp_id: None,
             status: ActionStatus::Loading,
             image: None,
+            images: Vec::new(),
             pending_code: None,
             backup_paths: None,
         }
     }
+
+    /// 本条消息当前应展示的图片列表：优先用 `images`，兼容旧的单图 `image` 字段
+    pub fn display_images(&self) -> Vec<String> {
+        if !self.images.is_empty() {
+            self.images.clone()
+        } else {
+            self.image.iter().cloned().collect()
+        }
+    }
+}
+
+/// 单个会话：一组消息 + 关联的最近操作文件
+///
+/// 之前整个 App 只有一条全局 `messages`，不同表格/任务的对话会混在一起。
+/// 引入 `Conversation` 之后，每张表格（或每个分析任务）可以拥有独立的
+/// 消息历史，互不干扰。
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Conversation {
+    pub id: Uuid,
+    pub title: String,
+    pub messages: Vec<ChatMessage>,
+    pub last_file_path: String,
+    /// Unix 时间戳（秒）
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Conversation {
+    /// 创建一条空的新会话
+    pub fn new(title: impl Into<String>) -> Self {
+        let now = now_unix();
+        Self {
+            id: Uuid::new_v4(),
+            title: title.into(),
+            messages: Vec::new(),
+            last_file_path: String::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// 用首条用户消息自动生成一个简短标题
+    ///
+    /// 取前 20 个字符，超出部分用省略号代替，避免侧边栏列表被撑爆。
+    pub fn auto_title_from(&mut self, first_user_message: &str) {
+        let trimmed = first_user_message.trim();
+        if trimmed.is_empty() {
+            return;
+        }
+        let title: String = trimmed.chars().take(20).collect();
+        self.title = if trimmed.chars().count() > 20 {
+            format!("{}…", title)
+        } else {
+            title
+        };
+    }
+
+    pub fn touch(&mut self) {
+        self.updated_at = now_unix();
+    }
+}
+
+fn now_unix() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// 多会话状态容器：持有所有会话 + 当前激活的会话 id
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AppState {
+    pub conversations: Vec<Conversation>,
+    pub active_id: Option<Uuid>,
+}
+
+impl AppState {
+    /// 初始状态：一条只带欢迎语的会话
+    pub fn new() -> Self {
+        let mut welcome = Conversation::new("新会话");
+        welcome
+            .messages
+            .push(ChatMessage::new(0, "👋 嗨！把 Excel 拖进来开始吧。", false));
+        let id = welcome.id;
+        Self {
+            conversations: vec![welcome],
+            active_id: Some(id),
+        }
+    }
+
+    pub fn active(&self) -> Option<&Conversation> {
+        self.active_id
+            .and_then(|id| self.conversations.iter().find(|c| c.id == id))
+    }
+
+    pub fn active_mut(&mut self) -> Option<&mut Conversation> {
+        let id = self.active_id?;
+        self.conversations.iter_mut().find(|c| c.id == id)
+    }
+
+    /// 新建一个空会话并将其设为当前激活会话，返回新会话 id
+    pub fn new_conversation(&mut self) -> Uuid {
+        let conv = Conversation::new("新会话");
+        let id = conv.id;
+        self.conversations.push(conv);
+        self.active_id = Some(id);
+        id
+    }
+
+    pub fn rename(&mut self, id: Uuid, new_title: impl Into<String>) {
+        if let Some(conv) = self.conversations.iter_mut().find(|c| c.id == id) {
+            conv.title = new_title.into();
+            conv.touch();
+        }
+    }
+
+    /// 删除一个会话；如果删的是当前激活会话，自动切换到列表中的第一个
+    pub fn delete(&mut self, id: Uuid) {
+        self.conversations.retain(|c| c.id != id);
+        if self.active_id == Some(id) {
+            self.active_id = self.conversations.first().map(|c| c.id);
+        }
+    }
+}
+
+/// 持久化 Python 内核一次代码执行的回执
+///
+/// 对应 `services::python` 里内核子进程通过 stdout 吐回来的一行 JSON：
+/// `{op_id, status, message, preview, stdout, image}`。`op_id` 由调用方生成，
+/// 只用来在内核收发两端对账，不需要在 Rust 侧保留，所以这里不建模该字段。
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PyExecResult {
+    /// "ok" 或 "error"
+    pub status: String,
+    /// 人类可读的结果摘要（成功提示 / 报错信息）
+    pub message: String,
+    /// 若代码产出了一张表格，这里是渲染好的 HTML 预览
+    pub preview: Option<String>,
+    /// 代码执行期间的原始 stdout 输出
+    pub stdout: Option<String>,
+    /// 若代码产出了图表，这里是 base64 编码的图片（data URL 或裸 base64）
+    pub image: Option<String>,
+}
+
+/// `services::python::read_sheet_grid` 解析出来的整张工作表
+///
+/// 行列都保持原始顺序；空单元格用 `None` 而不是空字符串，方便前端跟"这格本来
+/// 就没填"和"这格填的是空字符串"区分开。
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct SheetGrid {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+    /// 用户在 `SheetView` 里手动合并的区域；只影响展示，不改变 `rows` 本身的形状
+    #[serde(default)]
+    pub merges: Vec<CellRange>,
+}
+
+/// `active_files` 里一份文件的来源格式，由 `services::file_format::detect` 从
+/// 魔数（必要时回退到扩展名）推断出来，而不是简单看一眼文件后缀
+///
+/// 四种格式在 `services::python::read_sheet_grid` 里都会被解码成同一份
+/// [`SheetGrid`]，UI 和下游 Agent 代码不需要关心原始文件到底是哪种格式。
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Xlsx,
+    Xls,
+    Csv,
+    Ods,
+    /// 魔数和扩展名都认不出来，交给调用方决定要不要尝试硬解析
+    Unknown,
+}
+
+impl FileFormat {
+    /// `active_files` 工作区卡片上显示的小徽章文字
+    pub fn badge(&self) -> &'static str {
+        match self {
+            FileFormat::Xlsx => "XLSX",
+            FileFormat::Xls => "XLS",
+            FileFormat::Csv => "CSV",
+            FileFormat::Ods => "ODS",
+            FileFormat::Unknown => "?",
+        }
+    }
+}
+
+/// `SheetView` 里的单元格选区，行列都是 0-based、闭区间
+///
+/// 单选直接退化成 `row_start == row_end && col_start == col_end`，不用单独建一个
+/// "单选"枚举变体。
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct CellRange {
+    pub row_start: usize,
+    pub row_end: usize,
+    pub col_start: usize,
+    pub col_end: usize,
+}
+
+impl CellRange {
+    pub fn single(row: usize, col: usize) -> Self {
+        Self {
+            row_start: row,
+            row_end: row,
+            col_start: col,
+            col_end: col,
+        }
+    }
+
+    /// 把鼠标落点和拖拽终点整理成 `row_start <= row_end`、`col_start <= col_end` 的规范形式
+    pub fn normalized(anchor: (usize, usize), focus: (usize, usize)) -> Self {
+        Self {
+            row_start: anchor.0.min(focus.0),
+            row_end: anchor.0.max(focus.0),
+            col_start: anchor.1.min(focus.1),
+            col_end: anchor.1.max(focus.1),
+        }
+    }
+
+    pub fn is_single_cell(&self) -> bool {
+        self.row_start == self.row_end && self.col_start == self.col_end
+    }
+
+    /// 转成喂给 AI 的人类可读描述，比如 "B2" 或 "A1:C5"，用 Excel 习惯的字母列号
+    pub fn describe(&self) -> String {
+        let start = format!(
+            "{}{}",
+            Self::column_letter(self.col_start),
+            self.row_start + 1
+        );
+        if self.is_single_cell() {
+            start
+        } else {
+            let end = format!("{}{}", Self::column_letter(self.col_end), self.row_end + 1);
+            format!("{}:{}", start, end)
+        }
+    }
+
+    /// 0-based 列号转 Excel 习惯的字母列号（0 -> "A"，25 -> "Z"，26 -> "AA"）
+    ///
+    /// `describe` 和 `services::export` 的列宽/合并区计算共用这一套换算，避免
+    /// 两处各写一份容易漂移的进制转换。
+    pub fn column_letter(mut col: usize) -> String {
+        let mut s = String::new();
+        loop {
+            s.insert(0, (b'A' + (col % 26) as u8) as char);
+            if col < 26 {
+                break;
+            }
+            col = col / 26 - 1;
+        }
+        s
+    }
+}
+
+/// `services::sheet_history::EditHistory` 里一条可撤销/重做的操作
+///
+/// 每个变体只存"受影响区域"的最小前后快照（单元格、矩形区域或单独一行/列），
+/// 不会存整张表的副本，所以撤销/重做只需要按快照大小花时间，跟表本身有多大
+/// 无关。`SheetView` 每次编辑网格都生成一条，推进 [`crate::services::sheet_history::EditHistory`]
+/// 的撤销栈。
+#[derive(Debug, Clone, PartialEq)]
+pub enum SheetEdit {
+    /// 改了一个格子：`before`/`after` 都是 `Option`，跟 [`SheetGrid`] 本身"空格子
+    /// 用 `None`"的约定保持一致
+    CellEdit {
+        row: usize,
+        col: usize,
+        before: Option<String>,
+        after: Option<String>,
+    },
+    /// 往 `range` 这块矩形粘贴了新内容，`before`/`after` 都按行优先存成跟
+    /// `range` 同形状的二维数组
+    RangePaste {
+        range: CellRange,
+        before: Vec<Vec<Option<String>>>,
+        after: Vec<Vec<Option<String>>>,
+    },
+    /// 在 `at` 这一行之前插入了一整行空行
+    RowInsert { at: usize },
+    /// 删掉了第 `at` 行，`cells` 是删除前这一行的内容，撤销时原样塞回去
+    RowDelete {
+        at: usize,
+        cells: Vec<Option<String>>,
+    },
+    /// 在第 `at` 列之前插入了一整列空列
+    ColInsert { at: usize, header: String },
+    /// 删掉了第 `at` 列，`header`/`cells` 是删除前该列的表头和每一行对应的值
+    ColDelete {
+        at: usize,
+        header: String,
+        cells: Vec<Option<String>>,
+    },
+    /// 合并了 `range` 这块区域（撤销 = 取消合并）
+    Merge { range: CellRange },
+    /// 取消合并了 `range` 这块区域（撤销 = 重新合并）
+    Unmerge { range: CellRange },
+}
+
+/// 导出到 `.xlsx` 前的一张"结果表"：列头、数据行，以及需要合并的单元格区域
+///
+/// 由 `services::export::parse_html_table` 从聊天气泡里的结果表 HTML 解析而来；
+/// `merges` 复用 [`CellRange`]（跟 `SheetView` 选区是同一个类型），每个区间对应
+/// 原 HTML 里的一组 `colspan`/`rowspan`。
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ExportSheet {
+    pub name: String,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub merges: Vec<CellRange>,
+}
+
+/// 导入时每一列的类型，决定 `services::import_schema` 怎么把原始字符串强制转换
+///
+/// `Select` 额外带一份允许值列表：生成模板时拿它去建 Excel 原生的下拉数据验证；
+/// 导入时单元格原样存成字符串，不检查是否落在列表里。
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FieldType {
+    Text,
+    Number,
+    Date,
+    Select { options: Vec<String> },
+}
+
+/// 一条字段映射：原始表头（通常是中文） -> 喂给 Agent 的规范 key + 列类型
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FieldMapping {
+    /// 原始表头文本，比如 "姓名"，用来在导入的工作表里定位列
+    pub header: String,
+    /// 转换后 JSON 记录里用的 key，比如 "name"
+    pub key: String,
+    pub field_type: FieldType,
+}
+
+impl FieldMapping {
+    pub fn new() -> Self {
+        Self {
+            header: String::new(),
+            key: String::new(),
+            field_type: FieldType::Text,
+        }
+    }
+}
+
+/// 一份完整的导入 schema：一组字段映射，外加一个便于在设置里区分的名字
+///
+/// 没有被任何 [`FieldMapping`] 覆盖的原始列，`services::import_schema::import_rows`
+/// 导入时直接丢弃；没映射到值的行（比如全空行）也会被过滤掉。
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ImportSchema {
+    pub id: String,
+    pub name: String,
+    pub fields: Vec<FieldMapping>,
+}
+
+impl ImportSchema {
+    pub fn new() -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: "新导入模板".into(),
+            fields: Vec::new(),
+        }
+    }
+}
+
+/// `call_ai` 在 `auto_execute` 模式下，一次失败的自动修复尝试记录
+///
+/// 用来拼成前端可以展示的"修复轨迹"：模型第几次尝试、当时生成的是什么代码、
+/// 报错是什么，不含最终成功/放弃那一次（那次结果在 `AiReply::exec_result` 里）。
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RepairAttempt {
+    pub attempt: usize,
+    pub code: String,
+    pub error: String,
+}
+
+/// `ai::call_ai` 的结构化返回值
+///
+/// `reply_type` 区分这一轮到底是"闲聊"还是"生成了待确认的代码"：
+/// * `"code"` — `content` 是一段 Python 代码，前端展示"立即执行"按钮
+/// * `"chat"` — `content` 是纯文本回复，直接展示
+///
+/// `auto_execute` 模式下（见 [`crate::services::ai::call_ai`]），`content` 是最终
+/// 那一版代码（成功执行的，或者修复次数用完后原样交还的），`exec_result` 是它
+/// 对应的执行结果，`repair_log` 记录了中途失败又重新生成的每一次尝试。
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AiReply {
+    pub reply_type: String,
+    pub content: String,
+    #[serde(default)]
+    pub exec_result: Option<PyExecResult>,
+    #[serde(default)]
+    pub repair_log: Vec<RepairAttempt>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -98,6 +505,12 @@ pub struct ModelProfile {
     pub base_url: String,
     pub model_id: String,
     pub api_key: String,
+    /// 语音（STT/TTS）接口地址，留空时回退到 `base_url`
+    #[serde(default)]
+    pub voice_base_url: String,
+    /// 语音接口的 API Key，留空时回退到 `api_key`
+    #[serde(default)]
+    pub voice_api_key: String,
 }
 
 impl ModelProfile {
@@ -108,8 +521,67 @@ impl ModelProfile {
             base_url: "https://api.openai.com/v1".into(),
             model_id: "gpt-3.5-turbo".into(),
             api_key: "".into(),
+            voice_base_url: "".into(),
+            voice_api_key: "".into(),
         }
     }
+
+    /// 语音接口实际要用的 base_url：没单独配置就用聊天接口的那个
+    pub fn effective_voice_base_url(&self) -> &str {
+        if self.voice_base_url.trim().is_empty() {
+            &self.base_url
+        } else {
+            &self.voice_base_url
+        }
+    }
+
+    /// 语音接口实际要用的 API Key：没单独配置就用聊天接口的那个
+    pub fn effective_voice_api_key(&self) -> &str {
+        if self.voice_api_key.trim().is_empty() {
+            &self.api_key
+        } else {
+            &self.voice_api_key
+        }
+    }
+}
+
+/// 飞书（Feishu/Lark）云盘导出配置
+///
+/// 和 [`ModelProfile`] 一样挂在 [`AppConfig`] 下面，留空时 `feishu::upload_and_share`
+/// 直接返回错误提示用户先去设置里填。
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct FeishuConfig {
+    pub app_id: String,
+    pub app_secret: String,
+    /// 上传目标文件夹的 token（飞书云文档文件夹 URL 里的那一串）
+    pub parent_folder_token: String,
+}
+
+/// 用户在"设置"里选的主题偏好；`DockCapsule` 和 `Settings` 共用同一份偏好，
+/// `FollowSystem` 本身不是"浅色"或"深色"，具体显示哪个要在 `App` 里结合操作系统
+/// 当前外观现算一遍，参见 `main.rs` 里写 `data-theme` 属性的那段 `use_effect`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Light,
+    Dark,
+    FollowSystem,
+}
+
+impl Theme {
+    /// 设置页主题选择器里三个选项各自显示的文案
+    pub fn label(&self) -> &'static str {
+        match self {
+            Theme::Light => "浅色",
+            Theme::Dark => "深色",
+            Theme::FollowSystem => "跟随系统",
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::FollowSystem
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -117,6 +589,51 @@ pub struct AppConfig {
     pub profiles: Vec<ModelProfile>,
     pub active_profile_id: Option<String>,
     pub custom_prompt: Option<String>,
+    /// 是否自动朗读每一条 AI 回复
+    #[serde(default)]
+    pub auto_speak: bool,
+    /// 是否跳过"运行"确认，AI 生成代码后直接自动执行（并在报错时自愈重试）；
+    /// 默认关闭，用户需要先点"运行"看一眼代码再决定要不要执行
+    #[serde(default)]
+    pub auto_execute_code: bool,
+    /// 每个原始文件保留的热备份（`.bak`）数量，超出部分按时间戳从旧到新清理
+    #[serde(default = "default_backup_retention")]
+    pub backup_retention: usize,
+    /// 飞书云盘导出分享配置
+    #[serde(default)]
+    pub feishu: FeishuConfig,
+    /// 用户在设置里定义好的各份导入字段映射
+    #[serde(default)]
+    pub import_schemas: Vec<ImportSchema>,
+    /// 当前激活的导入 schema；文件拖入/打开时如果设了这个，就按它做类型化导入
+    #[serde(default)]
+    pub active_import_schema_id: Option<String>,
+    /// 主题偏好（浅色/深色/跟随系统），`DockCapsule` 和 `Settings` 共用
+    #[serde(default)]
+    pub theme: Theme,
+    /// 全局快捷键：把窗口从胶囊模式唤出到聊天主界面，格式是 `global-hotkey`
+    /// crate 认识的 "Ctrl+Alt+Space" 这种写法，`Settings` 里按一下实际组合键生成
+    #[serde(default = "default_hotkey_summon")]
+    pub hotkey_summon: String,
+    /// 全局快捷键：把窗口从聊天主界面收回胶囊模式
+    #[serde(default = "default_hotkey_dismiss")]
+    pub hotkey_dismiss: String,
+    /// 胶囊自动隐藏：不悬停/没置顶时滑到屏幕边缘只剩一条 peek 窄条，鼠标移近再
+    /// 弹回来，类似任务栏的自动隐藏；参见 `DockCapsule` 里的动画 effect
+    #[serde(default)]
+    pub auto_hide_dock: bool,
+}
+
+fn default_backup_retention() -> usize {
+    5
+}
+
+fn default_hotkey_summon() -> String {
+    "Ctrl+Alt+Space".into()
+}
+
+fn default_hotkey_dismiss() -> String {
+    "Ctrl+Alt+KeyD".into()
 }
 
 impl AppConfig {
@@ -127,14 +644,32 @@ impl AppConfig {
             base_url: "https://api.moonshot.cn/v1".into(),
             model_id: "moonshot-v1-8k".into(),
             api_key: "".into(),
+            voice_base_url: "".into(),
+            voice_api_key: "".into(),
         };
         Self {
             profiles: vec![default_profile.clone()],
             active_profile_id: Some("default".into()),
             custom_prompt: None,
+            auto_speak: false,
+            auto_execute_code: false,
+            backup_retention: default_backup_retention(),
+            feishu: FeishuConfig::default(),
+            import_schemas: Vec::new(),
+            active_import_schema_id: None,
+            theme: Theme::default(),
+            hotkey_summon: default_hotkey_summon(),
+            hotkey_dismiss: default_hotkey_dismiss(),
+            auto_hide_dock: false,
         }
     }
 
+    /// 当前激活的导入 schema（没选或选的已经被删了就是 `None`）
+    pub fn active_import_schema(&self) -> Option<&ImportSchema> {
+        let id = self.active_import_schema_id.as_ref()?;
+        self.import_schemas.iter().find(|s| &s.id == id)
+    }
+
     /// 获取当前激活的模型配置
     pub fn active_profile(&self) -> ModelProfile {
         if let Some(id) = &self.active_profile_id {
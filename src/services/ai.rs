@@ -1,10 +1,22 @@
-use crate::models::AppConfig;
+use crate::models::{
+    ActionStatus, AiReply, AppConfig, ChatMessage, ModelProfile, PyExecResult, RepairAttempt,
+};
 use crate::services::python;
 use anyhow::Result;
+use dioxus::prelude::Signal;
+use futures_util::StreamExt;
 use reqwest::{self, Client};
 use serde_json::{self, json, Value};
 use std::{fs::read_to_string, path::Path}; // 确保 main.rs 中有 mod services;
 
+/// 对话记忆里最多带上多少条历史消息
+///
+/// 太多了会把 Plan/Code 两步的 Prompt 预算挤爆，太少了又记不住"刚才的结果"，
+/// 6 条（约 3 轮问答）是个比较折中的窗口大小。
+const HISTORY_WINDOW: usize = 6;
+/// 系统消息里"最近已执行的操作"最多列几条
+const RECENT_OPS_LIMIT: usize = 5;
+
 /// 内部 helper: 读取 Prompt 模板
 fn load_prompt_template(filename: &str) -> String {
     let path = Path::new("assets").join(filename);
@@ -15,12 +27,12 @@ fn load_prompt_template(filename: &str) -> String {
     })
 }
 
-/// 内部 helper: 基础 LLM 调用
-async fn llm_request(config: &AppConfig, system_prompt: &str, user_prompt: &str) -> Result<String> {
+/// 内部 helper: 完整的多轮消息数组 LLM 调用
+///
+/// `llm_request` 是它在"system + user 两条消息"场景下的简化外壳；真正需要带
+/// 历史上下文的调用（见 `call_ai` 的编码步骤）直接拼好 `messages` 传进来。
+async fn llm_chat(config: &AppConfig, messages: Vec<Value>) -> Result<String> {
     let profile = config.active_profile();
-    let api_key = &profile.api_key;
-    let base_url = &profile.base_url;
-    let model = &profile.model_id;
 
     let client = Client::new();
 
@@ -28,16 +40,13 @@ async fn llm_request(config: &AppConfig, system_prompt: &str, user_prompt: &str)
     let response = client
         .post(format!(
             "{}/chat/completions",
-            base_url.trim_end_matches('/')
+            profile.base_url.trim_end_matches('/')
         ))
-        .header("Authorization", format!("Bearer {}", api_key))
+        .header("Authorization", format!("Bearer {}", profile.api_key))
         .header("Content-Type", "application/json")
         .json(&json!({
-            "model": model,
-            "messages": [
-                { "role": "system", "content": system_prompt },
-                { "role": "user", "content": user_prompt }
-            ],
+            "model": profile.model_id,
+            "messages": messages,
             "temperature": 0.1
         }))
         .send()
@@ -54,32 +63,167 @@ async fn llm_request(config: &AppConfig, system_prompt: &str, user_prompt: &str)
         .to_string())
 }
 
+/// 内部 helper: 基础 LLM 调用（单轮 system + user，不带历史）
+async fn llm_request(config: &AppConfig, system_prompt: &str, user_prompt: &str) -> Result<String> {
+    llm_chat(
+        config,
+        vec![
+            json!({ "role": "system", "content": system_prompt }),
+            json!({ "role": "user", "content": user_prompt }),
+        ],
+    )
+    .await
+}
+
+/// 截断到指定字符数，超出部分用省略号代替（用于系统消息里的简短摘要）
+fn truncate_chars(s: &str, max_chars: usize) -> String {
+    let trimmed = s.trim();
+    let truncated: String = trimmed.chars().take(max_chars).collect();
+    if trimmed.chars().count() > max_chars {
+        format!("{}…", truncated)
+    } else {
+        truncated
+    }
+}
+
+/// 从历史消息里摘出最近几条"成功执行"的 AI 回合，拼成一段简短摘要
+///
+/// 用于系统消息的"最近已执行的操作"部分，让模型知道会话里已经做过什么，
+/// 而不需要把完整历史再读一遍。
+fn recent_operations_summary(history: &[ChatMessage]) -> String {
+    let lines: Vec<String> = history
+        .iter()
+        .rev()
+        .filter(|m| !m.is_user && m.status == ActionStatus::Success && !m.text.trim().is_empty())
+        .take(RECENT_OPS_LIMIT)
+        .map(|m| format!("- {}", truncate_chars(&m.text, 80)))
+        .collect();
+
+    if lines.is_empty() {
+        "（暂无历史操作）".to_string()
+    } else {
+        let mut lines = lines;
+        lines.reverse();
+        lines.join("\n")
+    }
+}
+
+/// 把对话历史过滤/裁窗成可以直接塞进 `messages` 数组的多轮上下文
+///
+/// 只保留有实际内容、且不是"加载中/等待确认/出错"这类瞬时状态的消息
+/// （用户消息本身没有 status 语义，始终保留），按时间顺序截取最近
+/// [`HISTORY_WINDOW`] 条。
+fn build_history_messages(history: &[ChatMessage]) -> Vec<Value> {
+    let mut windowed: Vec<&ChatMessage> = history
+        .iter()
+        .filter(|m| !m.text.trim().is_empty() && (m.is_user || m.status == ActionStatus::Success))
+        .rev()
+        .take(HISTORY_WINDOW)
+        .collect();
+    windowed.reverse();
+
+    windowed
+        .into_iter()
+        .map(|m| {
+            json!({
+                "role": if m.is_user { "user" } else { "assistant" },
+                "content": m.text,
+            })
+        })
+        .collect()
+}
+
+/// 组装持久化的系统消息：编码规范模板 + 当前文件 + 列结构 + 最近操作摘要
+///
+/// 这条系统消息在每一轮编码请求里都会重新生成，让模型既知道"怎么写代码"
+/// (`coder_tmpl`)，也知道"现在在跟哪个文件打交道、表长什么样、之前做过什么"。
+fn build_system_message(
+    coder_tmpl: &str,
+    file_path: &str,
+    columns_summary: &str,
+    history: &[ChatMessage],
+) -> String {
+    let file_name = Path::new(file_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| file_path.to_string());
+
+    format!(
+        "{coder_tmpl}\n\n---\n【当前文件】{file_name}\n【列结构（含所在 Sheet）】\n{columns_summary}\n\n【最近已执行的操作】\n{recent_ops}",
+        coder_tmpl = coder_tmpl,
+        file_name = file_name,
+        columns_summary = columns_summary,
+        recent_ops = recent_operations_summary(history),
+    )
+}
+
 /// 主入口: 智能 Re-Act 循环 (生成代码版)
 ///
 /// 逻辑：
-/// 1. 侦察 (Peek) -> 2. 规划 (Plan) -> 3. 编码 (Code) -> 4. 返回前端 (Return)
-/// **注意：不自动执行代码，交由用户确认。**
+/// 1. 侦察 (Peek) -> 2. 规划 (Plan) -> 3. 编码 (Code) -> 4. 返回前端 / 闭环自愈 (Return)
+///
+/// # 会话记忆 (Conversation Memory)
+///
+/// 以前每次调用都是从零拼一条 Prompt，模型完全不知道这个会话里之前做过什么。
+/// 现在编码这一步会带上：
+/// * `columns_summary` — 列结构（通常来自 [`python::get_excel_columns`] 的检索结果），
+///   替代裸的 "Structure Config"。
+/// * `history` — 当前会话里最近几轮成功执行的 `ChatMessage`，裁成一个滚动窗口，
+///   原样作为多轮 `messages` 传给模型，而不是压扁成一段文字。
+///
+/// # 闭环自愈 (`auto_execute`)
+///
+/// `run_python_code` 回执里的 `status`/`message` 报错信息本来就能用来触发上游自动修复，但以前
+/// 一直没人接上——代码生成完就直接甩给用户，错没错全靠用户自己读。
+/// `auto_execute = true` 时，STEP 4 生成代码后会立刻执行：成功就直接把结果带
+/// 回去；失败就把原始需求、失败的代码和报错信息喂回编码 Prompt 重新生成，最多
+/// 重试 `max_repair_attempts` 次，每次尝试都记进返回值的 `repair_log` 里，供 UI
+/// 展示完整的修复轨迹；重试次数用完了还没成功，就把最后一版代码原样（连同它
+/// 的报错）交还给用户，而不是假装成功。
+///
+/// `auto_execute = false` 时行为和以前完全一样：生成完代码直接返回，不执行。
+///
+/// # 流式闲聊 (`messages`/`chat_msg_id`)
+///
+/// 纯闲聊分支（没有文件上下文，或者 `prompt_coder.md` 缺失时的降级兜底）不会
+/// 产出代码，不需要走 STEP 1-5 的 Re-Act 流程，所以干脆直接用
+/// [`stream_chat_completion`] 把回复逐 token 写进 `messages[chat_msg_id]`，
+/// 而不是等整句话生成完再一次性返回——调用方（`InputArea`）只需要提前在
+/// `messages` 里占好这一条消息的位置，传进来的 id 够不够新由调用方保证。
 pub async fn call_ai(
     config: &AppConfig,
+    file_path: &str,
     user_content: &str,
-    context_file_path: Option<String>,
-) -> Result<String> {
-    // 1. 如果没有文件上下文，直接进行普通闲聊
-    let file_path = match context_file_path {
-        Some(path) => path,
-        None => {
-            // 使用默认 System Prompt
-            let sys_prompt = load_prompt_template("system_prompt.md");
-            return llm_request(config, &sys_prompt, user_content).await;
-        }
-    };
+    columns_summary: &str,
+    history: &[ChatMessage],
+    auto_execute: bool,
+    max_repair_attempts: usize,
+    messages: Signal<Vec<ChatMessage>>,
+    chat_msg_id: usize,
+) -> Result<AiReply> {
+    // 1. 如果没有文件上下文，直接进行普通闲聊（流式）
+    if file_path.is_empty() {
+        let sys_prompt = load_prompt_template("system_prompt.md");
+        stream_chat_completion(config, &sys_prompt, user_content, messages, chat_msg_id).await?;
+        let reply = messages
+            .read()
+            .get(chat_msg_id)
+            .map(|m| m.text.clone())
+            .unwrap_or_default();
+        return Ok(AiReply {
+            reply_type: "chat".into(),
+            content: reply,
+            exec_result: None,
+            repair_log: Vec::new(),
+        });
+    }
 
     println!("🚀 启动 Re-Act 生成流程: {}", file_path);
 
     // --- STEP 1: 感知 (Peek) ---
     // 调用 Python 获取前 20 行数据指纹，用于辅助决策
     println!("👀 [Step 1] 正在侦察 Excel 结构...");
-    let peek_json_str = python::peek_excel(&file_path)
+    let peek_json_str = python::peek_excel(file_path)
         .await
         .unwrap_or_else(|e| format!("{{'status': 'error', 'msg': '{}'}}", e));
 
@@ -101,40 +245,263 @@ pub async fn call_ai(
     println!("💡 规划结果: {}", plan_json);
 
     // --- STEP 3: 编码 (Code) ---
-    // 根据规划结果生成最终 Python 代码
+    // 根据规划结果 + 列结构 + 会话记忆生成最终 Python 代码
     println!("💻 [Step 3] 正在生成代码...");
     let coder_tmpl = load_prompt_template("prompt_coder.md");
 
-    // 如果没有 coder 模板，回退到默认 prompt
+    // 如果没有 coder 模板，回退到默认 prompt（不带历史，降级为单轮闲聊，流式）
     if coder_tmpl.is_empty() {
         let sys_prompt = load_prompt_template("system_prompt.md");
-        let fallback_ctx = format!("Target File: {}\nStructure Hint: {}", file_path, plan_json);
-        return llm_request(
+        let fallback_ctx = format!(
+            "Target File: {}\nStructure Hint: {}\nColumns: {}",
+            file_path, plan_json, columns_summary
+        );
+        stream_chat_completion(
             config,
             &sys_prompt,
             &format!("{}\n\nContext:\n{}", user_content, fallback_ctx),
+            messages,
+            chat_msg_id,
         )
-        .await;
+        .await?;
+        let reply = messages
+            .read()
+            .get(chat_msg_id)
+            .map(|m| m.text.clone())
+            .unwrap_or_default();
+        return Ok(AiReply {
+            reply_type: "chat".into(),
+            content: reply,
+            exec_result: None,
+            repair_log: Vec::new(),
+        });
     }
 
+    // 注入文件路径
+    let coder_tmpl_filled = coder_tmpl.replace("{file_path}", &file_path.replace("\\", "\\\\"));
+    let system_message =
+        build_system_message(&coder_tmpl_filled, file_path, columns_summary, history);
+
     let user_msg_code = format!(
         "Structure Config: {}\nUser Query: {}",
         plan_json, user_content
     );
 
-    // 注入文件路径
-    let coder_tmpl_filled = coder_tmpl.replace("{file_path}", &file_path.replace("\\", "\\\\"));
+    let mut chat_messages = vec![json!({ "role": "system", "content": system_message })];
+    chat_messages.extend(build_history_messages(history));
+    chat_messages.push(json!({ "role": "user", "content": user_msg_code }));
 
-    let code_response = llm_request(config, &coder_tmpl_filled, &user_msg_code).await?;
+    let code_response = llm_chat(config, chat_messages).await?;
 
     // --- STEP 4: 返回 (Return) ---
-    // 直接返回生成的 Markdown 代码块。
-    // 前端 UI 会识别 ```python，并显示“运行”按钮。
-    println!("✅ 代码生成完毕，等待用户确认");
+    if !auto_execute {
+        // 直接返回生成的 Markdown 代码块。
+        // 前端 UI 会识别 ```python，并显示“立即执行”按钮。
+        println!("✅ 代码生成完毕，等待用户确认");
+        return Ok(AiReply {
+            reply_type: "code".into(),
+            content: code_response,
+            exec_result: None,
+            repair_log: Vec::new(),
+        });
+    }
+
+    // --- STEP 5: 闭环自愈 (auto_execute ReAct 修复循环) ---
+    println!("🔁 [Step 5] auto_execute 开启，执行代码并在失败时自动修复...");
+    let mut current_code = code_response;
+    let mut repair_log: Vec<RepairAttempt> = Vec::new();
+    let mut attempt = 0usize;
+
+    loop {
+        let exec_result = exec_code_once(file_path, &current_code).await?;
+
+        if exec_result.status != "error" {
+            println!("✅ 自动执行成功（第 {} 次尝试）", attempt + 1);
+            return Ok(AiReply {
+                reply_type: "code".into(),
+                content: current_code,
+                exec_result: Some(exec_result),
+                repair_log,
+            });
+        }
+
+        println!("⚠️ 第 {} 次执行失败: {}", attempt + 1, exec_result.message);
+
+        if attempt >= max_repair_attempts {
+            println!(
+                "🤯 已达最大修复次数 ({})，放弃自动修复，交还最后一版代码",
+                max_repair_attempts
+            );
+            return Ok(AiReply {
+                reply_type: "code".into(),
+                content: current_code,
+                exec_result: Some(exec_result),
+                repair_log,
+            });
+        }
+
+        repair_log.push(RepairAttempt {
+            attempt: attempt + 1,
+            code: current_code.clone(),
+            error: exec_result.message.clone(),
+        });
+
+        // 把原始需求、失败的代码和报错信息一起喂回编码 Prompt，让模型带着上下文修
+        let repair_user_msg = format!(
+            "刚才生成的代码执行报错了，请分析原因并重新生成修正后的完整代码。\n\n原始需求: {}\n\n失败的代码:\n{}\n\n报错信息:\n{}",
+            user_content, current_code, exec_result.message
+        );
+        let mut retry_messages = vec![json!({ "role": "system", "content": system_message })];
+        retry_messages.extend(build_history_messages(history));
+        retry_messages.push(json!({ "role": "user", "content": repair_user_msg }));
+
+        current_code = llm_chat(config, retry_messages).await?;
+        attempt += 1;
+    }
+}
+
+/// 在阻塞线程池里跑一次生成的代码，解析出 [`PyExecResult`]
+///
+/// `run_python_code` 走的是持久内核的同步 stdin/stdout 协议，必须用
+/// `spawn_blocking` 包一层，不能直接在异步上下文里调用。
+async fn exec_code_once(file_path: &str, code: &str) -> Result<PyExecResult> {
+    let file_path = file_path.to_string();
+    let code = code.to_string();
+    let op_id = uuid::Uuid::new_v4().to_string();
+
+    let json_str =
+        tokio::task::spawn_blocking(move || python::run_python_code(&file_path, &code, &op_id))
+            .await
+            .map_err(|e| anyhow::anyhow!("Python 执行线程崩溃: {}", e))?;
+
+    Ok(
+        serde_json::from_str(&json_str).unwrap_or_else(|e| PyExecResult {
+            status: "error".into(),
+            message: format!("内部结果解析失败: {}", e),
+            preview: None,
+            stdout: None,
+            image: None,
+        }),
+    )
+}
+
+/// 以 SSE 流式方式调用 OpenAI 兼容接口，将增量 token 实时写入目标消息
+///
+/// 逐行读取响应体，剥离 `data: ` 前缀，忽略 `[DONE]` 哨兵，解析
+/// `choices[0].delta.content` 并追加到 `messages[msg_id].text`，
+/// 同时把该消息状态置为 `ActionStatus::Streaming`（并清掉占位文字），供 UI 实时渲染。
+pub async fn stream_chat_completion(
+    config: &AppConfig,
+    system_prompt: &str,
+    user_prompt: &str,
+    mut messages: Signal<Vec<ChatMessage>>,
+    msg_id: usize,
+) -> Result<()> {
+    let profile = config.active_profile();
+    let client = Client::new();
+
+    let response = client
+        .post(format!(
+            "{}/chat/completions",
+            profile.base_url.trim_end_matches('/')
+        ))
+        .header("Authorization", format!("Bearer {}", profile.api_key))
+        .header("Content-Type", "application/json")
+        .json(&json!({
+            "model": profile.model_id,
+            "stream": true,
+            "messages": [
+                { "role": "system", "content": system_prompt },
+                { "role": "user", "content": user_prompt }
+            ],
+            "temperature": 0.1
+        }))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let err_text = response.text().await?;
+        if let Some(msg) = messages.write().get_mut(msg_id) {
+            msg.status = ActionStatus::Error(err_text.clone());
+        }
+        return Err(anyhow::anyhow!("API Error: {}", err_text));
+    }
+
+    if let Some(msg) = messages.write().get_mut(msg_id) {
+        msg.status = ActionStatus::Streaming;
+        // 占位消息自带"正在思考..."，不清掉的话回复正文会接在它后面
+        msg.text.clear();
+    }
+
+    let mut byte_stream = response.bytes_stream();
+    // SSE 帧以换行分隔，但可能跨多个 chunk 到达，这里维护一个行缓冲区
+    let mut line_buf = String::new();
+
+    while let Some(chunk) = byte_stream.next().await {
+        let chunk = chunk?;
+        line_buf.push_str(&String::from_utf8_lossy(&chunk));
+
+        while let Some(pos) = line_buf.find('\n') {
+            let line = line_buf[..pos].trim().to_string();
+            line_buf.drain(..=pos);
+
+            let Some(data) = line.strip_prefix("data: ") else {
+                continue;
+            };
+            if data == "[DONE]" {
+                continue;
+            }
+            if data.is_empty() {
+                continue;
+            }
+
+            if let Ok(value) = serde_json::from_str::<Value>(data) {
+                if let Some(delta) = value["choices"][0]["delta"]["content"].as_str() {
+                    if !delta.is_empty() {
+                        if let Some(msg) = messages.write().get_mut(msg_id) {
+                            msg.text.push_str(delta);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(msg) = messages.write().get_mut(msg_id) {
+        if msg.status == ActionStatus::Streaming {
+            msg.status = ActionStatus::Success;
+        }
+    }
+
+    Ok(())
+}
 
-    // 可选：在返回内容前加一点分析摘要，让用户知道 AI 是怎么想的
-    // let final_response = format!("**分析完毕**：检测到表格结构配置为 `{}`。\n\n{}", plan_json, code_response);
+/// 设置页"测试连接"按钮专用：发一次最小化的 chat completions 请求，只看能不能
+/// 拿到 2xx 响应，不关心回复内容，成功时返回耗时（毫秒）方便用户判断网络延迟
+pub async fn test_connection(profile: &ModelProfile) -> Result<u128> {
+    let client = Client::new();
+    let start = std::time::Instant::now();
+
+    let response = client
+        .post(format!(
+            "{}/chat/completions",
+            profile.base_url.trim_end_matches('/')
+        ))
+        .header("Authorization", format!("Bearer {}", profile.api_key))
+        .header("Content-Type", "application/json")
+        .json(&json!({
+            "model": profile.model_id,
+            "messages": [{ "role": "user", "content": "ping" }],
+            "max_tokens": 1
+        }))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let err_text = response.text().await.unwrap_or_default();
+        return Err(anyhow::anyhow!("HTTP {}: {}", status, err_text));
+    }
 
-    // 为了保持界面简洁，直接返回代码部分即可，或者只包含必要的解释
-    Ok(code_response)
+    Ok(start.elapsed().as_millis())
 }
@@ -0,0 +1,281 @@
+//! 列语义检索
+//!
+//! 宽表场景下 `call_ai` 需要知道"有哪些列"才能生成靠谱的代码，但如果把几百个
+//! 列的表头全部塞进 Prompt，很快就会把上下文预算挤爆。这里的做法：
+//!
+//! 1. 打开文件时，给每一列生成一段"指纹文本"（表头 + 抽样单元格 + 所在 Sheet 名），
+//!    调用当前激活模型的 embeddings 接口算出向量，连同列名一起存成矩阵。
+//! 2. 真正提问时，把用户的 Prompt 也 embed 成同一维度的向量，用余弦相似度
+//!    （先做 L2 归一化，再点积）挑出 Top-K 最相关的列，只把这些列的上下文喂给 AI。
+//! 3. 按 `(file_path, mtime)` 把向量缓存到磁盘，重新打开同一个文件不用重新算。
+//! 4. 列数不超过阈值时完全跳过检索，直接走原来的"全量列表"行为，省一次网络往返。
+
+use crate::services::config;
+use directories::ProjectDirs;
+use ndarray::{Array1, Array2, Axis};
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+const APP_QUALIFIER: &str = "com";
+const APP_ORG: &str = "excelagent";
+const APP_NAME: &str = "excel_agent";
+
+/// 列数不超过这个阈值时不走检索，直接把全部列喂给 AI
+const RETRIEVAL_THRESHOLD: usize = 40;
+/// 检索命中后取回的列数量
+const TOP_K: usize = 25;
+/// 每一列抽样几个单元格值拼进指纹文本
+const SAMPLE_ROWS: usize = 3;
+
+/// 一列的"指纹"：它所在的 Sheet、列名，以及喂给 embedding 接口的那段文本
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ColumnFingerprint {
+    sheet: String,
+    column: String,
+    dtype: String,
+    text: String,
+}
+
+/// 磁盘缓存：按文件的 mtime 失效
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct EmbeddingCache {
+    mtime_secs: u64,
+    columns: Vec<ColumnFingerprint>,
+    /// 行优先存储，第 i 行对应 `columns[i]` 的向量
+    vectors: Array2<f32>,
+}
+
+fn cache_dir() -> PathBuf {
+    let dir = if let Some(proj_dirs) = ProjectDirs::from(APP_QUALIFIER, APP_ORG, APP_NAME) {
+        proj_dirs.data_local_dir().join("column_embeddings")
+    } else {
+        PathBuf::from("column_embeddings_cache")
+    };
+    let _ = fs::create_dir_all(&dir);
+    dir
+}
+
+/// 用文件路径的哈希做缓存文件名，避免路径分隔符/中文字符污染文件系统
+fn cache_path(file_path: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    file_path.hash(&mut hasher);
+    cache_dir().join(format!("{:x}.json", hasher.finish()))
+}
+
+fn file_mtime_secs(file_path: &str) -> u64 {
+    fs::metadata(file_path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// 读取工作簿所有 Sheet 的列指纹：表头 + 前几行抽样单元格 + Sheet 名
+///
+/// 直接用 PyO3 同步调用（调用方已经身处 `spawn_blocking` 线程里，不需要再套一层异步）。
+fn read_column_fingerprints(file_path: &str) -> Result<Vec<ColumnFingerprint>, String> {
+    Python::with_gil(|py| {
+        let code = format!(
+            r#"
+import pandas as pd
+import json
+
+path = r"{}"
+out = []
+try:
+    sheets = pd.read_excel(path, sheet_name=None, nrows={})
+    for sheet_name, df in sheets.items():
+        for col in df.columns:
+            dtype = str(df[col].dtype)
+            samples = [str(v) for v in df[col].dropna().head({}).tolist()]
+            text = f"Sheet: {{sheet_name}} | Column: {{col}} | Type: {{dtype}} | Samples: {{', '.join(samples)}}"
+            out.append({{"sheet": sheet_name, "column": str(col), "dtype": dtype, "text": text}})
+except Exception as e:
+    out = {{"__error__": str(e)}}
+
+print(json.dumps(out))
+"#,
+            file_path, SAMPLE_ROWS, SAMPLE_ROWS
+        );
+
+        let sys = py.import("sys").map_err(|e| e.to_string())?;
+        let io = py.import("io").map_err(|e| e.to_string())?;
+        let stdout_capture = io.call_method0("StringIO").map_err(|e| e.to_string())?;
+        sys.setattr("stdout", stdout_capture)
+            .map_err(|e| e.to_string())?;
+
+        py.run(&code, None, None).map_err(|e| e.to_string())?;
+
+        let output = stdout_capture
+            .call_method0("getvalue")
+            .map_err(|e| e.to_string())?
+            .extract::<String>()
+            .map_err(|e| e.to_string())?;
+
+        serde_json::from_str::<Vec<ColumnFingerprint>>(output.trim())
+            .map_err(|e| format!("解析列指纹失败: {} (原始输出: {})", e, output))
+    })
+}
+
+/// 调用当前激活模型的 embeddings 接口，把一批文本编码成向量
+fn embed_texts(texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+    if texts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let cfg = config::load_config();
+    let profile = cfg.active_profile();
+
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .post(format!(
+            "{}/embeddings",
+            profile.base_url.trim_end_matches('/')
+        ))
+        .header("Authorization", format!("Bearer {}", profile.api_key))
+        .header("Content-Type", "application/json")
+        .json(&serde_json::json!({
+            "model": profile.model_id,
+            "input": texts,
+        }))
+        .send()
+        .map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Embeddings API 返回非成功状态: {}",
+            response.status()
+        ));
+    }
+
+    let body: serde_json::Value = response.json().map_err(|e| e.to_string())?;
+    let data = body["data"]
+        .as_array()
+        .ok_or_else(|| "Embeddings 响应缺少 data 字段".to_string())?;
+
+    data.iter()
+        .map(|item| {
+            item["embedding"]
+                .as_array()
+                .map(|vec| {
+                    vec.iter()
+                        .filter_map(|v| v.as_f64().map(|f| f as f32))
+                        .collect()
+                })
+                .ok_or_else(|| "Embeddings 响应缺少 embedding 字段".to_string())
+        })
+        .collect()
+}
+
+/// 读磁盘缓存；命中且 mtime 一致则直接用，否则重新计算并写回缓存
+fn load_or_build_embeddings(file_path: &str) -> Result<EmbeddingCache, String> {
+    let mtime = file_mtime_secs(file_path);
+    let path = cache_path(file_path);
+
+    if let Ok(content) = fs::read_to_string(&path) {
+        if let Ok(cache) = serde_json::from_str::<EmbeddingCache>(&content) {
+            if cache.mtime_secs == mtime {
+                return Ok(cache);
+            }
+        }
+    }
+
+    let columns = read_column_fingerprints(file_path)?;
+    let texts: Vec<String> = columns.iter().map(|c| c.text.clone()).collect();
+    let vectors_raw = embed_texts(&texts)?;
+
+    let dim = vectors_raw.first().map(|v| v.len()).unwrap_or(0);
+    let mut vectors = Array2::<f32>::zeros((vectors_raw.len(), dim));
+    for (i, row) in vectors_raw.into_iter().enumerate() {
+        for (j, value) in row.into_iter().enumerate() {
+            vectors[[i, j]] = value;
+        }
+    }
+
+    let cache = EmbeddingCache {
+        mtime_secs: mtime,
+        columns,
+        vectors,
+    };
+
+    if let Ok(json) = serde_json::to_string(&cache) {
+        let _ = fs::write(&path, json);
+    }
+
+    Ok(cache)
+}
+
+fn l2_normalize(vec: &mut Array1<f32>) {
+    let norm = vec.dot(vec).sqrt();
+    if norm > f32::EPSILON {
+        *vec /= norm;
+    }
+}
+
+/// 余弦相似度排序后取 Top-K 下标（按相似度从高到低）
+fn top_k_indices(query: &Array1<f32>, matrix: &Array2<f32>, k: usize) -> Vec<usize> {
+    let mut normalized_query = query.clone();
+    l2_normalize(&mut normalized_query);
+
+    let mut scored: Vec<(usize, f32)> = matrix
+        .axis_iter(Axis(0))
+        .enumerate()
+        .map(|(idx, row)| {
+            let mut row = row.to_owned();
+            l2_normalize(&mut row);
+            (idx, row.dot(&normalized_query))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored.into_iter().take(k).map(|(idx, _)| idx).collect()
+}
+
+fn format_columns(columns: &[&ColumnFingerprint]) -> String {
+    columns
+        .iter()
+        .map(|c| format!("[{}] {}({})", c.sheet, c.column, c.dtype))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 给定文件和用户的提问，返回喂给 AI 的列上下文
+///
+/// 列数不超过 [`RETRIEVAL_THRESHOLD`] 时直接返回全部列；否则计算/复用缓存的
+/// embedding 矩阵，按与 `user_query` 的余弦相似度取 Top-K 列。任何一步出错
+/// 都会降级为全量列表，保证上层流程始终拿到可用的上下文而不是直接失败。
+pub fn relevant_columns_context(file_path: &str, user_query: &str) -> String {
+    let columns = match read_column_fingerprints(file_path) {
+        Ok(columns) => columns,
+        Err(e) => return format!("❌ 无法读取列结构: {}", e),
+    };
+    if columns.len() <= RETRIEVAL_THRESHOLD {
+        return format_columns(&columns.iter().collect::<Vec<_>>());
+    }
+
+    let cache = match load_or_build_embeddings(file_path) {
+        Ok(c) => c,
+        Err(e) => {
+            println!("⚠️ 列 embedding 检索失败，降级为全量列表: {}", e);
+            return format_columns(&columns.iter().collect::<Vec<_>>());
+        }
+    };
+
+    let query_vec = match embed_texts(&[user_query.to_string()]) {
+        Ok(mut vectors) if !vectors.is_empty() => Array1::from(vectors.remove(0)),
+        _ => {
+            // Embedding 用户问题失败：退化为全量列表，而不是让整个请求失败
+            return format_columns(&cache.columns.iter().collect::<Vec<_>>());
+        }
+    };
+
+    let indices = top_k_indices(&query_vec, &cache.vectors, TOP_K);
+    let selected: Vec<&ColumnFingerprint> = indices.iter().map(|&i| &cache.columns[i]).collect();
+    format_columns(&selected)
+}
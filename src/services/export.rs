@@ -0,0 +1,201 @@
+//! 把 Agent 产出的结果表导出成带格式的 `.xlsx`
+//!
+//! 聊天气泡里的结果表（见 `components::chat_view`）是 Python 侧 `DataFrame.to_html()`
+//! 吐出来、再经 ammonia 净化的一段 HTML，这里反过来把它解析回结构化数据，再用
+//! 纯 Rust 的 `umya-spreadsheet` 写成 `.xlsx`：表头加底色、按列估算列宽、数据区
+//! 加细边框，`colspan`/`rowspan` 原样转成 `merge_cells`。一次导出可以带多张表，
+//! 各占一个 sheet。
+//!
+//! 整个 workbook（所有 sheet 的全部单元格）先在内存里建好，再由
+//! `umya_spreadsheet::writer::xlsx::write` 一次写到磁盘；没有流式写出，内存占用
+//! 随结果行数增长。
+
+use crate::models::{CellRange, ExportSheet};
+use scraper::{Html, Selector};
+use umya_spreadsheet::{
+    helper::coordinate::CellCoordinates, Border, Color, HorizontalAlignmentValues,
+};
+
+const HEADER_FILL: &str = "FFB8CCE4";
+
+/// 把 `<table>...</table>` 风格的 HTML 解析成一张 [`ExportSheet`]
+///
+/// 只认标准的 `<tr>/<th>/<td>` 结构；解析不出任何行（比如压根没有 `<table>`
+/// 标签）时返回 `None`，调用方据此隐藏/跳过导出入口。
+pub fn parse_html_table(html: &str, sheet_name: &str) -> Option<ExportSheet> {
+    let fragment = Html::parse_fragment(html);
+    let row_sel = Selector::parse("tr").ok()?;
+    let cell_sel = Selector::parse("th,td").ok()?;
+
+    let mut headers = Vec::new();
+    let mut rows = Vec::new();
+    let mut merges = Vec::new();
+    // 被上方某格 rowspan 占住的 (行, 列)：这些位置在本行里补空串，让值和合并区对齐
+    let mut covered = std::collections::HashSet::new();
+
+    for (row_idx, tr) in fragment.select(&row_sel).enumerate() {
+        let mut col_idx = 0usize;
+        let mut row_cells = Vec::new();
+        let mut row_has_header = false;
+
+        for cell in tr.select(&cell_sel) {
+            while covered.contains(&(row_idx, col_idx)) {
+                row_cells.push(String::new());
+                col_idx += 1;
+            }
+            let text = cell.text().collect::<String>().trim().to_string();
+            let colspan: usize = cell
+                .value()
+                .attr("colspan")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1)
+                .max(1);
+            let rowspan: usize = cell
+                .value()
+                .attr("rowspan")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1)
+                .max(1);
+
+            if colspan > 1 || rowspan > 1 {
+                merges.push(CellRange {
+                    row_start: row_idx,
+                    row_end: row_idx + rowspan - 1,
+                    col_start: col_idx,
+                    col_end: col_idx + colspan - 1,
+                });
+            }
+            for r in row_idx + 1..row_idx + rowspan {
+                covered.extend((col_idx..col_idx + colspan).map(|c| (r, c)));
+            }
+
+            if cell.value().name() == "th" {
+                row_has_header = true;
+            }
+            row_cells.push(text);
+            // colspan 跨过的其余列补空串，后面的格子才落在合并区右侧
+            row_cells.resize(row_cells.len() + colspan - 1, String::new());
+            col_idx += colspan;
+        }
+
+        // 第一个含 <th> 的行当表头，其余按数据行处理——跟 pandas `to_html(index=False)`
+        // 的输出结构一致（唯一的表头行套在 <thead> 里，清一色 <th>）
+        if headers.is_empty() && row_has_header {
+            headers = row_cells;
+        } else if !row_cells.is_empty() {
+            rows.push(row_cells);
+        }
+    }
+
+    if headers.is_empty() && rows.is_empty() {
+        return None;
+    }
+
+    Some(ExportSheet {
+        name: sheet_name.to_string(),
+        headers,
+        rows,
+        merges,
+    })
+}
+
+/// 把若干张结果表写成一个 `.xlsx`，每张表各占一个 sheet
+pub fn export_sheets_to_xlsx(sheets: &[ExportSheet], out_path: &str) -> Result<(), String> {
+    if sheets.is_empty() {
+        return Err("没有可导出的结果表".into());
+    }
+
+    let mut book = umya_spreadsheet::new_file_empty_worksheet();
+
+    for (idx, sheet) in sheets.iter().enumerate() {
+        let sheet_name = if sheet.name.is_empty() {
+            format!("结果{}", idx + 1)
+        } else {
+            sheet.name.clone()
+        };
+        book.new_sheet(&sheet_name)
+            .map_err(|e| format!("创建工作表 {} 失败: {}", sheet_name, e))?;
+        let ws = book
+            .get_sheet_by_name_mut(&sheet_name)
+            .ok_or_else(|| format!("找不到刚创建的工作表 {}", sheet_name))?;
+
+        // 表头行：底色 + 加粗 + 居中 + 底边框
+        for (col, header) in sheet.headers.iter().enumerate() {
+            let cell = ws.get_cell_mut(CellCoordinates::from((col as u32 + 1, 1u32)));
+            cell.set_value(header);
+            let style = cell.get_style_mut();
+            style.set_background_color(Color::from_hex(HEADER_FILL));
+            style.get_font_mut().set_bold(true);
+            style
+                .get_alignment_mut()
+                .set_horizontal(HorizontalAlignmentValues::Center);
+            style
+                .get_borders_mut()
+                .get_bottom_mut()
+                .set_border_style(Border::BORDER_THIN);
+        }
+
+        // 数据行：细边框围住整个数据区
+        for (row_idx, row) in sheet.rows.iter().enumerate() {
+            for (col_idx, value) in row.iter().enumerate() {
+                let cell = ws.get_cell_mut(CellCoordinates::from((
+                    col_idx as u32 + 1,
+                    row_idx as u32 + 2,
+                )));
+                cell.set_value(value);
+                let borders = cell.get_style_mut().get_borders_mut();
+                borders.get_top_mut().set_border_style(Border::BORDER_THIN);
+                borders
+                    .get_bottom_mut()
+                    .set_border_style(Border::BORDER_THIN);
+                borders.get_left_mut().set_border_style(Border::BORDER_THIN);
+                borders
+                    .get_right_mut()
+                    .set_border_style(Border::BORDER_THIN);
+            }
+        }
+
+        // 列宽按"表头和该列最长一格内容的字符数"估算，够用就行，不追求精确排版
+        let col_count = sheet
+            .headers
+            .len()
+            .max(sheet.rows.iter().map(|r| r.len()).max().unwrap_or(0));
+        for col in 0..col_count {
+            let header_len = sheet
+                .headers
+                .get(col)
+                .map(|h| h.chars().count())
+                .unwrap_or(0);
+            let max_cell_len = sheet
+                .rows
+                .iter()
+                .filter_map(|r| r.get(col))
+                .map(|c| c.chars().count())
+                .max()
+                .unwrap_or(0);
+            let width = (header_len.max(max_cell_len) as f64 + 2.0).max(8.0);
+            ws.get_column_dimension_mut(&CellRange::column_letter(col))
+                .set_width(width);
+        }
+
+        // 原 HTML 里的 colspan/rowspan 还原成合并区
+        for range in &sheet.merges {
+            let start = format!(
+                "{}{}",
+                CellRange::column_letter(range.col_start),
+                range.row_start + 1
+            );
+            let end = format!(
+                "{}{}",
+                CellRange::column_letter(range.col_end),
+                range.row_end + 1
+            );
+            let _ = ws.add_merge_cells(format!("{}:{}", start, end));
+        }
+    }
+
+    // `new_file_empty_worksheet()` 本身不带占位 Sheet1（跟 `new_file()` 不一样），
+    // 上面 new_sheet 出来的都是真正的数据表，不需要再删一张
+    umya_spreadsheet::writer::xlsx::write(&book, std::path::Path::new(out_path))
+        .map_err(|e| format!("写出 .xlsx 失败: {}", e))
+}
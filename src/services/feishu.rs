@@ -0,0 +1,185 @@
+//! 飞书（Feishu/Lark）云盘导出服务
+//!
+//! 把生成好的 `.xlsx`（比如 `run_batch_hot_undo` 或 AI 生成代码处理完的结果文件）
+//! 上传到飞书云文档并开启分享权限，返回一个可以直接甩给同事的链接，免去手动
+//! 复制文件的麻烦。
+//!
+//! 走的是飞书开放平台文档里的标准流程：
+//! 1. `auth/v3/tenant_access_token/internal` 换取 `tenant_access_token`（有效期内缓存复用，避免每次上传都重新鉴权）。
+//! 2. `drive/v1/files/upload_all` 把整个文件传到指定的父文件夹，换回 `file_token`。
+//! 3. `drive/v2/permissions/{token}/public` 把权限改成组织内可读，这样链接发出去同事不用额外申请权限。
+//! 4. `drive/v1/metas/batch_query` 用 `file_token` 查出真正的文档地址——`file_token`
+//!    本身不是链接，`open.feishu.cn` 也只是 API 域名，不能直接拼出可访问的地址。
+
+use crate::models::FeishuConfig;
+use reqwest::multipart;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+const API_BASE: &str = "https://open.feishu.cn/open-apis";
+
+/// 缓存住的 tenant_access_token，附带一个"提前量"过期时间
+struct CachedToken {
+    token: String,
+    expires_at: Instant,
+}
+
+fn token_cache() -> &'static Mutex<Option<CachedToken>> {
+    static CACHE: OnceLock<Mutex<Option<CachedToken>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(None))
+}
+
+/// 获取 tenant_access_token：缓存未过期就直接复用，否则重新换取一个
+async fn get_tenant_access_token(config: &FeishuConfig) -> Result<String, String> {
+    if let Some(cached) = token_cache().lock().unwrap().as_ref() {
+        if cached.expires_at > Instant::now() {
+            return Ok(cached.token.clone());
+        }
+    }
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/auth/v3/tenant_access_token/internal", API_BASE))
+        .json(&json!({
+            "app_id": config.app_id,
+            "app_secret": config.app_secret,
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let body: Value = response.json().await.map_err(|e| e.to_string())?;
+    if body["code"].as_i64() != Some(0) {
+        return Err(format!("获取飞书 tenant_access_token 失败: {}", body));
+    }
+    let token = body["tenant_access_token"]
+        .as_str()
+        .ok_or_else(|| "飞书响应里没有 tenant_access_token 字段".to_string())?
+        .to_string();
+
+    // 接口返回的 expire 是秒数，提前 60 秒过期留出网络往返的余量
+    let expire_secs = body["expire"].as_u64().unwrap_or(7200).saturating_sub(60);
+    *token_cache().lock().unwrap() = Some(CachedToken {
+        token: token.clone(),
+        expires_at: Instant::now() + Duration::from_secs(expire_secs),
+    });
+
+    Ok(token)
+}
+
+/// 把文件整份上传到飞书云文档的指定父文件夹，返回新文件的 `file_token`
+async fn upload_file(
+    access_token: &str,
+    parent_folder_token: &str,
+    path: &Path,
+) -> Result<String, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("读取待上传文件失败: {}", e))?;
+    let size = bytes.len();
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("export.xlsx")
+        .to_string();
+
+    let form = multipart::Form::new()
+        .text("file_name", file_name)
+        .text("parent_type", "explorer")
+        .text("parent_node", parent_folder_token.to_string())
+        .text("size", size.to_string())
+        .part(
+            "file",
+            multipart::Part::bytes(bytes).file_name("upload.xlsx"),
+        );
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/drive/v1/files/upload_all", API_BASE))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let body: Value = response.json().await.map_err(|e| e.to_string())?;
+    if body["code"].as_i64() != Some(0) {
+        return Err(format!("飞书文件上传失败: {}", body));
+    }
+    body["data"]["file_token"]
+        .as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| "飞书上传响应里没有 file_token 字段".to_string())
+}
+
+/// 查询文件元数据，拿到飞书返回的真实可分享链接
+///
+/// `upload_all` 的响应里只有 `file_token`，并不是一个可以直接打开的文档地址；
+/// `open.feishu.cn` 是开放平台的 API 域名，不是云文档域名，不能靠拼接
+/// `{API_BASE}/drive/file/{token}` 这种方式伪造链接。真正的分享地址要通过
+/// 元数据接口用 `doc_token` 换。
+async fn fetch_file_url(access_token: &str, file_token: &str) -> Result<String, String> {
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/drive/v1/metas/batch_query", API_BASE))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .json(&json!({
+            "request_docs": [{
+                "doc_token": file_token,
+                "doc_type": "file",
+            }],
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let body: Value = response.json().await.map_err(|e| e.to_string())?;
+    if body["code"].as_i64() != Some(0) {
+        return Err(format!("查询飞书文件链接失败: {}", body));
+    }
+    body["data"]["metas"]
+        .get(0)
+        .and_then(|meta| meta["url"].as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(|| "飞书元数据响应里没有 url 字段".to_string())
+}
+
+/// 把刚上传的文件权限改成"组织内获得链接的人可阅读"
+async fn grant_public_read(access_token: &str, file_token: &str) -> Result<(), String> {
+    let client = reqwest::Client::new();
+    let response = client
+        .patch(format!(
+            "{}/drive/v2/permissions/{}/public?type=file",
+            API_BASE, file_token
+        ))
+        .header("Authorization", format!("Bearer {}", access_token))
+        .json(&json!({
+            "link_share_entity": "tenant_readable",
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let body: Value = response.json().await.map_err(|e| e.to_string())?;
+    if body["code"].as_i64() != Some(0) {
+        return Err(format!("开放飞书分享权限失败: {}", body));
+    }
+    Ok(())
+}
+
+/// 上传一个处理结果文件到飞书云文档，并把权限开放给组织内所有人，返回分享链接
+pub async fn upload_and_share(path: &str) -> Result<String, String> {
+    let config = crate::services::config::load_config().feishu;
+    if config.app_id.trim().is_empty() || config.app_secret.trim().is_empty() {
+        return Err("尚未在设置里配置飞书 app_id / app_secret".to_string());
+    }
+    if config.parent_folder_token.trim().is_empty() {
+        return Err("尚未在设置里配置飞书目标文件夹 token".to_string());
+    }
+
+    let access_token = get_tenant_access_token(&config).await?;
+    let file_token =
+        upload_file(&access_token, &config.parent_folder_token, Path::new(path)).await?;
+    grant_public_read(&access_token, &file_token).await?;
+    fetch_file_url(&access_token, &file_token).await
+}
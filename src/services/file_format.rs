@@ -0,0 +1,76 @@
+//! 按魔数（必要时回退到扩展名）识别拖入/打开的表格文件到底是哪种格式
+//!
+//! `.xlsx` 和 `.ods` 都是 zip 容器，光看前几个字节分不出来，需要再往里瞅一眼
+//! zip 条目名；`.xls` 是 OLE2 复合文档，有自己的魔数；魔数对不上的（比如纯文本
+//! `.csv`）按扩展名判断，扩展名也认不出来就是 `Unknown`。识别结果用来决定
+//! `services::python::read_sheet_grid` 该用哪条 pandas 读取路径，以及给工作区
+//! 文件卡片挂一个徽章。
+
+use crate::models::FileFormat;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+const OLE2_MAGIC: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+
+/// 识别 `path` 指向的文件格式
+///
+/// 优先看文件头的魔数；魔数读取失败（文件不存在/没权限）时退回按扩展名猜，
+/// 扩展名也认不出来就是 [`FileFormat::Unknown`]。
+pub fn detect(path: &str) -> FileFormat {
+    match sniff_magic(path) {
+        Some(format) => format,
+        None => from_extension(path),
+    }
+}
+
+/// 读文件头几个字节判断容器类型；zip 容器再展开看一眼内部条目名区分 xlsx/ods
+fn sniff_magic(path: &str) -> Option<FileFormat> {
+    let file = File::open(path).ok()?;
+    let mut reader = BufReader::new(file);
+    let mut header = [0u8; 8];
+    let read = reader.read(&mut header).ok()?;
+    if read < 4 {
+        return None;
+    }
+
+    if header[..8] == OLE2_MAGIC {
+        return Some(FileFormat::Xls);
+    }
+    if header[..4] == ZIP_MAGIC {
+        return Some(sniff_zip_kind(path).unwrap_or(FileFormat::Xlsx));
+    }
+    None
+}
+
+/// zip 容器内部：ODS 第一条目固定是未压缩的 `mimetype`，内容是
+/// `application/vnd.oasis.opendocument.spreadsheet`；xlsx 没有这个条目
+fn sniff_zip_kind(path: &str) -> Option<FileFormat> {
+    let file = File::open(path).ok()?;
+    let mut archive = zip::ZipArchive::new(file).ok()?;
+    let mut mimetype = archive.by_name("mimetype").ok()?;
+    let mut contents = String::new();
+    mimetype.read_to_string(&mut contents).ok()?;
+    if contents.trim() == "application/vnd.oasis.opendocument.spreadsheet" {
+        Some(FileFormat::Ods)
+    } else {
+        Some(FileFormat::Xlsx)
+    }
+}
+
+/// 魔数读不出来时（比如纯文本的 `.csv` 没有统一的文件头）按扩展名兜底
+fn from_extension(path: &str) -> FileFormat {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match ext.as_str() {
+        "xlsx" | "xlsm" => FileFormat::Xlsx,
+        "xls" => FileFormat::Xls,
+        "ods" => FileFormat::Ods,
+        "csv" => FileFormat::Csv,
+        _ => FileFormat::Unknown,
+    }
+}
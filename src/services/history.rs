@@ -0,0 +1,120 @@
+//! 会话历史持久化
+//!
+//! 在此之前 `Conversation.messages` 只存在于内存里，应用重启后
+//! （包括撤销所需的 `backup_paths`）全部丢失。这里把每个会话的消息
+//! 落盘为一个 JSON Lines 文件（一行一条 `ChatMessage`，字段与内存结构
+//! 完全一致），另外维护一份轻量的会话索引文件（只含标题/路径/时间戳，
+//! 不含消息本体）用于应用启动时快速列出所有会话。
+//!
+//! 出于简单性考虑：消息在被执行/撤销后会原地变更 `status` 等字段，
+//! 这里没有维护真正的"仅追加"日志（那样需要按 id 定位并重写某一行，
+//! 增加不必要的复杂度），而是在每次消息集合变化时整体重写该会话的
+//! 日志文件。文件仍然是 JSON Lines 格式，只是"重写"而非"追加"。
+
+use crate::models::{ChatMessage, Conversation};
+use directories::ProjectDirs;
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+const APP_QUALIFIER: &str = "com";
+const APP_ORG: &str = "excelagent";
+const APP_NAME: &str = "excel_agent";
+
+/// 每页默认加载的消息条数
+pub const PAGE_SIZE: usize = 30;
+
+fn history_dir() -> PathBuf {
+    let dir = if let Some(proj_dirs) = ProjectDirs::from(APP_QUALIFIER, APP_ORG, APP_NAME) {
+        proj_dirs.data_local_dir().join("conversations")
+    } else {
+        PathBuf::from("conversations")
+    };
+    if !dir.exists() {
+        let _ = fs::create_dir_all(&dir);
+    }
+    dir
+}
+
+fn index_path() -> PathBuf {
+    history_dir().join("index.json")
+}
+
+fn log_path(conversation_id: Uuid) -> PathBuf {
+    history_dir().join(format!("{}.jsonl", conversation_id))
+}
+
+/// 加载会话索引（仅元信息，不含消息），用于启动时重建会话列表
+pub fn load_index() -> Vec<Conversation> {
+    let path = index_path();
+    if let Ok(content) = fs::read_to_string(&path) {
+        if let Ok(convs) = serde_json::from_str::<Vec<Conversation>>(&content) {
+            return convs;
+        }
+    }
+    Vec::new()
+}
+
+/// 保存会话索引（不含消息本体，消息单独落在各自的 jsonl 里）
+pub fn save_index(conversations: &[Conversation]) {
+    let light: Vec<Conversation> = conversations
+        .iter()
+        .map(|c| Conversation {
+            messages: Vec::new(),
+            ..c.clone()
+        })
+        .collect();
+    if let Ok(json) = serde_json::to_string_pretty(&light) {
+        let _ = fs::write(index_path(), json);
+    }
+}
+
+/// 整体重写某个会话的消息日志
+pub fn save_messages(conversation_id: Uuid, messages: &[ChatMessage]) {
+    let mut body = String::new();
+    for msg in messages {
+        if let Ok(line) = serde_json::to_string(msg) {
+            body.push_str(&line);
+            body.push('\n');
+        }
+    }
+    let _ = fs::write(log_path(conversation_id), body);
+}
+
+fn read_all_messages(conversation_id: Uuid) -> Vec<ChatMessage> {
+    let path = log_path(conversation_id);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<ChatMessage>(line).ok())
+        .collect()
+}
+
+/// 加载最新的一页消息（供 `ChatView` 初次打开时使用），以及是否还有更早的历史
+pub fn load_latest(conversation_id: Uuid, page_size: usize) -> (Vec<ChatMessage>, bool) {
+    let all = read_all_messages(conversation_id);
+    if all.len() <= page_size {
+        (all, false)
+    } else {
+        let start = all.len() - page_size;
+        (all[start..].to_vec(), true)
+    }
+}
+
+/// 向上滚动触发：加载 `already_loaded` 条之前的更早一页
+pub fn load_older_page(
+    conversation_id: Uuid,
+    already_loaded: usize,
+    page_size: usize,
+) -> (Vec<ChatMessage>, bool) {
+    let all = read_all_messages(conversation_id);
+    if already_loaded >= all.len() {
+        return (Vec::new(), false);
+    }
+    let end = all.len() - already_loaded;
+    let start = end.saturating_sub(page_size);
+    let has_more = start > 0;
+    (all[start..end].to_vec(), has_more)
+}
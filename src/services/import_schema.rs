@@ -0,0 +1,166 @@
+//! 按用户声明的 [`ImportSchema`] 把原始工作表转换成干净的、类型化的 JSON 记录
+//!
+//! 输入是 `services::python::read_sheet_grid` 解析出来的 [`SheetGrid`]（跟
+//! `SheetView` 预览用的是同一份数据），没有被任何 [`FieldMapping`] 覆盖的原始
+//! 列直接丢弃，空行也过滤掉，这样喂给 Agent 的就是干净的结构化数据而不是一整
+//! 张连表头都要自己猜的原始表格。
+//!
+//! 反过来，[`generate_template_xlsx`] 用同一份 schema 生成一个空的 `.xlsx`：
+//! 表头原样写出，`select` 类型的列额外加一条 Excel 原生的下拉数据验证，用户
+//! 只能从声明好的选项里选，保证填回来的东西能再导入一轮而不出岔子。
+
+use crate::models::{FieldType, ImportSchema, SheetGrid};
+use serde_json::{Map, Value};
+use umya_spreadsheet::{
+    helper::coordinate::CellCoordinates, Border, Color, DataValidation, DataValidationValues,
+    HorizontalAlignmentValues,
+};
+
+const HEADER_FILL: &str = "FFB8CCE4";
+/// 留给"正式导入前先照着模板填"的富余行数——模板本身是空的，这只是给数据
+/// 验证圈定一个够用的范围，不代表模板自带这么多空行
+const TEMPLATE_VALIDATION_ROWS: u32 = 1000;
+
+/// 按 `schema` 把 `grid` 转成 JSON 记录
+///
+/// 返回 `(records, skipped_empty_rows)`：后者是因为所有映射字段都读出空值而被
+/// 丢弃的行数，方便调用方在提示里告诉用户"N 行没对上任何数据，被跳过了"。
+pub fn import_rows(grid: &SheetGrid, schema: &ImportSchema) -> (Vec<Map<String, Value>>, usize) {
+    // 表头 -> 列下标，一份映射可能重复出现某个表头名就取第一次命中的列
+    let col_of = |header: &str| grid.headers.iter().position(|h| h == header);
+
+    let mut records = Vec::with_capacity(grid.rows.len());
+    let mut skipped = 0usize;
+
+    for row in &grid.rows {
+        let mut record = Map::new();
+        for field in &schema.fields {
+            let Some(col) = col_of(&field.header) else {
+                continue;
+            };
+            let raw = row.get(col).and_then(|c| c.as_deref()).unwrap_or("").trim();
+            if raw.is_empty() {
+                continue;
+            }
+            record.insert(field.key.clone(), coerce(raw, &field.field_type));
+        }
+
+        if record.is_empty() {
+            skipped += 1;
+        } else {
+            records.push(record);
+        }
+    }
+
+    (records, skipped)
+}
+
+/// 按列类型把原始字符串强制转换成 JSON 值
+///
+/// - `Number`: 解析不出数字就保留原字符串，不让一个脏单元格直接丢数据
+/// - `Select`: 原样存成字符串，不检查是否落在允许值列表里，留给上层（或 Agent）去发现异常
+/// - `Text`/`Date`: 原样当字符串存（日期格式五花八门，这里不强行归一化，交给
+///   下游 pandas/Agent 按需解析）
+fn coerce(raw: &str, field_type: &FieldType) -> Value {
+    match field_type {
+        FieldType::Number => raw
+            .parse::<f64>()
+            .map(|n| {
+                serde_json::Number::from_f64(n)
+                    .map(Value::Number)
+                    .unwrap_or(Value::String(raw.into()))
+            })
+            .unwrap_or_else(|_| Value::String(raw.into())),
+        FieldType::Text | FieldType::Date => Value::String(raw.into()),
+        FieldType::Select { .. } => Value::String(raw.into()),
+    }
+}
+
+/// 生成一份空的 `.xlsx` 模板：表头来自 `schema.fields`，`select` 列带下拉校验
+pub fn generate_template_xlsx(schema: &ImportSchema, out_path: &str) -> Result<(), String> {
+    if schema.fields.is_empty() {
+        return Err("这份 schema 还没有任何字段映射".into());
+    }
+
+    let mut book = umya_spreadsheet::new_file();
+    let ws = book
+        .get_sheet_by_name_mut("Sheet1")
+        .ok_or_else(|| "模板工作簿缺少默认 Sheet1".to_string())?;
+
+    for (col, field) in schema.fields.iter().enumerate() {
+        let cell = ws.get_cell_mut(CellCoordinates::from((col as u32 + 1, 1u32)));
+        cell.set_value(&field.header);
+        let style = cell.get_style_mut();
+        style.set_background_color(Color::from_hex(HEADER_FILL));
+        style.get_font_mut().set_bold(true);
+        style
+            .get_alignment_mut()
+            .set_horizontal(HorizontalAlignmentValues::Center);
+        style
+            .get_borders_mut()
+            .get_bottom_mut()
+            .set_border_style(Border::BORDER_THIN);
+
+        if let FieldType::Select { options } = &field.field_type {
+            add_select_validation(ws, col as u32, options);
+        }
+    }
+
+    umya_spreadsheet::writer::xlsx::write(&book, std::path::Path::new(out_path))
+        .map_err(|e| format!("写出模板失败: {}", e))
+}
+
+/// 给某一列挂一条"从列表取值"的数据验证，覆盖表头下方 `TEMPLATE_VALIDATION_ROWS` 行
+///
+/// Excel 内联列表公式（`"选项1,选项2,..."`）有 255 字符的长度上限，选项一多就会
+/// 超限，所以选项数量或总长度超过阈值时改成把选项写到一个远离数据区的辅助列
+/// （`$ZZ$1:$ZZ$N`），再用区域引用当 `formula1`，绕开这个限制。
+fn add_select_validation(ws: &mut umya_spreadsheet::Worksheet, col: u32, options: &[String]) {
+    let inline_list = options.join(",");
+    let formula1 = if options.len() <= 20 && inline_list.len() <= 250 {
+        format!("\"{}\"", inline_list)
+    } else {
+        const HELPER_COL: u32 = 702; // ZZ 列，远离正常数据区
+        for (i, opt) in options.iter().enumerate() {
+            ws.get_cell_mut(CellCoordinates::from((HELPER_COL, i as u32 + 1)))
+                .set_value(opt);
+        }
+        format!(
+            "${0}$1:${0}${1}",
+            crate::models::CellRange::column_letter((HELPER_COL - 1) as usize),
+            options.len()
+        )
+    };
+
+    let start = format!("{}2", crate::models::CellRange::column_letter(col as usize));
+    let end = format!(
+        "{}{}",
+        crate::models::CellRange::column_letter(col as usize),
+        TEMPLATE_VALIDATION_ROWS + 1
+    );
+
+    let mut validation = DataValidation::default();
+    validation.set_type(DataValidationValues::List);
+    validation.set_formula1(formula1);
+    validation.set_sequence_of_references(umya_spreadsheet::SequenceOfReferences::from(
+        format!("{}:{}", start, end).as_str(),
+    ));
+    ws.get_data_validations_mut()
+        .add_data_validation_list(validation);
+}
+
+/// 预览用：把 schema 应用到 `grid` 后生成一段人类可读的摘要，塞进聊天消息里
+pub fn summarize_import(
+    schema: &ImportSchema,
+    records: &[Map<String, Value>],
+    skipped: usize,
+) -> String {
+    let fields: Vec<&str> = schema.fields.iter().map(|f| f.key.as_str()).collect();
+    format!(
+        "📥 已按模板「{}」导入 {} 条记录（字段：{}），跳过 {} 行空行",
+        schema.name,
+        records.len(),
+        fields.join(", "),
+        skipped
+    )
+}
@@ -0,0 +1,11 @@
+pub mod ai;
+pub mod config;
+pub mod embeddings;
+pub mod export;
+pub mod feishu;
+pub mod file_format;
+pub mod history;
+pub mod import_schema;
+pub mod python;
+pub mod sheet_history;
+pub mod voice;
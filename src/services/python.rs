@@ -11,12 +11,16 @@
 //!    - 现在: 遍历备份文件中的所有 Sheet 进行全量恢复。
 //!    - 安全机制: 恢复的内容标绿，新增的内容（不在备份中）标红并保留，绝不自动删除用户数据。
 
+use crate::models::PyExecResult;
 use pyo3::prelude::*;
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::io;
+use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
-use std::sync::Once;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex, Once, OnceLock};
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
@@ -117,119 +121,183 @@ fn unzip_file(zip_path: &Path, dest_dir: &Path) -> io::Result<()> {
 
 /// 启动时清理旧的备份文件
 ///
-/// 每次应用启动时调用，防止 `backups/` 文件夹无限膨胀。
+/// 以前的做法是每次启动都 `remove_dir_all` 整个 `backups/` 目录，这导致热撤销
+/// 只能覆盖"当前这一次运行"，重启应用就等于清空撤销历史。现在改为按
+/// `AppConfig::backup_retention` 做滚动保留：每个原始文件只留下最近 N 个
+/// `.bak` 快照，多出来的按时间戳从旧到新删除，其余文件原样保留。
 pub fn cleanup_backups() {
     let backup_dir = Path::new("backups");
-    // 如果存在，先删除整个目录（清空旧文件）
-    if backup_dir.exists() {
-        let _ = fs::remove_dir_all(backup_dir);
-    }
-    // 重新创建空目录
     if let Err(e) = fs::create_dir_all(backup_dir) {
         println!("⚠️ 无法创建备份目录: {}", e);
-    } else {
-        println!("✅ 备份目录已重置: backups/");
+        return;
     }
+
+    let retention = crate::services::config::load_config().backup_retention;
+    prune_all_backups(backup_dir, retention);
+    println!(
+        "✅ 备份目录已按保留策略清理: backups/ (retention = {})",
+        retention
+    );
 }
 
-/// 异步运行 Python 代码
+/// 解析 `<name>.<timestamp>.bak` 格式的备份文件名，返回 (原始文件名, 时间戳)
+fn parse_backup_name(file_name: &str) -> Option<(String, u128)> {
+    let stripped = file_name.strip_suffix(".bak")?;
+    let (original, ts) = stripped.rsplit_once('.')?;
+    let ts: u128 = ts.parse().ok()?;
+    Some((original.to_string(), ts))
+}
+
+/// 对 `backup_dir` 下的所有备份按原始文件名分组，每组只保留最近 `keep` 个，
+/// 其余按时间戳从旧到新删除。
+fn prune_all_backups(backup_dir: &Path, keep: usize) {
+    let entries = match fs::read_dir(backup_dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let mut grouped: HashMap<String, Vec<(u128, std::path::PathBuf)>> = HashMap::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if let Some((original, ts)) = parse_backup_name(file_name) {
+            grouped.entry(original).or_default().push((ts, path));
+        }
+    }
+
+    for (_, mut snapshots) in grouped {
+        prune_snapshots(&mut snapshots, keep);
+    }
+}
+
+/// 对同一个原始文件的 `.bak` 列表保留最近 `keep` 个，删除更旧的其余部分
+fn prune_snapshots(snapshots: &mut Vec<(u128, std::path::PathBuf)>, keep: usize) {
+    snapshots.sort_by(|a, b| b.0.cmp(&a.0));
+    for (_, path) in snapshots.iter().skip(keep) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// 异步运行一次性 Python 代码片段（xlwings 热备份/热撤销等内部脚本专用）
 ///
-/// # 功能增强 (Robustness Upgrade)
+/// # 结构化异常捕获 (Structured Exception Upgrade)
 ///
-/// 为了防止 AI 生成的代码 "吞掉" 异常 (即 try...except print error)，
-/// 本函数实现了**双流检测**机制：
-/// 1. **Stderr 检测**: 捕获解释器级别的 Crash 和 traceback。
-/// 2. **Stdout 关键词检测**: 扫描输出中是否包含 "Error", "Exception", "❌" 等关键词。
+/// 以前靠"把 stdout 小写后搜关键词（`"error:"`、`"not found"` 等）"来猜有没有
+/// 出错，这套启发式有两个硬伤：
+/// 1. **假阳性**: 正常业务输出里只要提到"not found"这种词就会被误判为失败。
+/// 2. **假阴性**: 完全看不懂中文报错；而且代码里 `stdout`/`stderr` 其实被设成了
+///    *同一个* `StringIO`，"分离双流检测"从一开始就没生效过。
 ///
-/// 任何一种情况命中，都会返回 `Err`，从而触发上层的自动修复逻辑。
-pub async fn run_python_code(code: &str) -> Result<String, String> {
+/// 现在把用户代码包进一段 harness：在 harness 内部用 `exec()` + `try/except`
+/// 真正捕获异常对象，借 `traceback` 模块定位报错行号，拼成一个结构化字典
+/// `{ok, exc_type, exc_msg, lineno, stdout, stderr}`，打印到一条专用的"哨兵
+/// 通道"（harness 运行期间，用户代码的 `sys.stdout`/`sys.stderr` 被各自重定向
+/// 到独立的 `StringIO`，和这条哨兵通道互不干扰）。Rust 侧只需要解析这一行 JSON，
+/// 不用再猜关键词，拿到的 `exc_type`/`lineno` 也能喂给上层的自动修复逻辑。
+///
+/// 每次调用都会启动一个全新的解释器上下文，脚本执行完即销毁——这正适合这里的
+/// 调用方（它们都是一次性的 xlwings/物理文件操作脚本，不需要跨调用保留状态）。
+/// AI 回合的代码执行走的是下面持久化的 [`run_python_code`]，两者不要混用。
+async fn run_python_snippet(code: &str) -> Result<String, String> {
     let code = code.to_string();
 
     // 放入 blocking 线程池，防止卡死 UI
-    let result = tokio::task::spawn_blocking(move || {
+    let result = tokio::task::spawn_blocking(move || -> PyResult<serde_json::Value> {
         Python::with_gil(|py| {
-            let sys = py.import("sys").map_err(|e| e.to_string())?;
-            let io = py.import("io").map_err(|e| e.to_string())?;
-
-            // 1. 分离标准输出 (stdout) 和 标准错误 (stderr)
-            let stdout_capture = io.call_method0("StringIO").map_err(|e| e.to_string())?;
-            let stderr_capture = io.call_method0("StringIO").map_err(|e| e.to_string())?;
-            // 劫持标准输出
-            sys.setattr("stdout", stdout_capture)
-                .map_err(|e| e.to_string())?;
-            sys.setattr("stderr", stdout_capture)
-                .map_err(|e| e.to_string())?;
-
-            // 2. 执行代码
-            let run_result = py.run(&code, None, None);
-
-            // 3. 提取输出
-            let stdout_str = stdout_capture
-                .call_method0("getvalue")
-                .unwrap()
-                .extract::<String>()
-                .unwrap_or_default();
-            let stderr_str = stderr_capture
-                .call_method0("getvalue")
-                .unwrap()
-                .extract::<String>()
-                .unwrap_or_default();
-
-            // 4. 智能错误判断逻辑
-            // 情况 A: Python 解释器直接抛出异常 (硬错误)
-            if let Err(e) = run_result {
-                let full_err = format!(
-                    "🐍 Runtime Exception:\n{}\n\n📝 Stderr Trace:\n{}",
-                    e, stderr_str
-                );
-                return Err(full_err);
-            }
+            let sys = py.import("sys")?;
+            let io = py.import("io")?;
 
-            // 情况 B: 检查 Stderr 是否包含严重错误关键词
-            if !stderr_str.trim().is_empty() {
-                let lower_err = stderr_str.to_lowercase();
-                if lower_err.contains("error")
-                    || lower_err.contains("exception")
-                    || lower_err.contains("traceback")
-                {
-                    // 如果 stderr 里有明显的错误词，视为失败
-                    return Err(format!("⚠️ Detected Error in Stderr:\n{}", stderr_str));
-                }
-            }
+            let prev_stdout = sys.getattr("stdout")?;
+            let prev_stderr = sys.getattr("stderr")?;
 
-            // 情况 C: 检查 Stdout 是否包含“软错误”关键词 (AI 吞掉了异常 print 出来的情况)
-            let lower_out = stdout_str.to_lowercase();
-            // 关键词黑名单：只要出现这些词，就认为脚本执行结果是不符合预期的
-            let error_keywords = [
-                "error:",          // 通用错误
-                "exception:",      // 异常
-                "traceback (most", // 堆栈
-                "failed to",       // 失败
-                "attributeerror",  // 常见属性错误
-                "keyerror",        // 键错误
-                "valueerror",      // 值错误
-                "not found",       // 文件未找到
-                "❌",              // AI 习惯用的 emoji
-            ];
-
-            for kw in error_keywords {
-                if lower_out.contains(kw) {
-                    // 发现疑似错误，返回 Err 触发重试
-                    // 把 stdout 原样返回作为错误信息，让 AI 看到它打印了什么
-                    return Err(stdout_str);
-                }
-            }
+            // 哨兵通道：harness 跑完之后，唯一一行结构化 JSON 就写在这里，
+            // 和被 harness 临时接管、给用户代码用的 stdout/stderr 彻底分开
+            let sentinel_channel = io.call_method0("StringIO")?;
+            sys.setattr("stdout", sentinel_channel)?;
+
+            let globals = pyo3::types::PyDict::new(py);
+            globals.set_item("__user_code__", code.as_str())?;
+
+            let harness = r#"
+import sys, io, json, traceback
+
+_dedicated_channel = sys.stdout
+_stdout_buf = io.StringIO()
+_stderr_buf = io.StringIO()
+sys.stdout = _stdout_buf
+sys.stderr = _stderr_buf
+
+_result = {"ok": True, "exc_type": None, "exc_msg": None, "lineno": None}
+try:
+    exec(compile(__user_code__, "<ai_snippet>", "exec"), globals())
+except Exception as _e:
+    _lineno = None
+    for _frame in traceback.extract_tb(_e.__traceback__):
+        if _frame.filename == "<ai_snippet>":
+            _lineno = _frame.lineno
+    _result["ok"] = False
+    _result["exc_type"] = type(_e).__name__
+    _result["exc_msg"] = str(_e)
+    _result["lineno"] = _lineno
+
+sys.stdout = _dedicated_channel
+sys.stderr = sys.__stderr__
+
+_result["stdout"] = _stdout_buf.getvalue()
+_result["stderr"] = _stderr_buf.getvalue()
+
+print(json.dumps(_result), file=_dedicated_channel)
+"#;
+
+            let run_result = py.run(harness, Some(globals), None);
 
-            // 一切正常
-            Ok(stdout_str)
+            // 不管 harness 跑没跑成功，都要先把 sys.stdout/stderr 恢复原样，
+            // 避免污染后续调用（比如下一次 run_python_snippet）
+            sys.setattr("stdout", prev_stdout)?;
+            sys.setattr("stderr", prev_stderr)?;
+
+            // harness 自身理论上不会再抛异常（用户代码的异常已经在里面兜住了），
+            // 真抛了说明是 harness 脚本本身的 bug，直接把 PyErr 冒泡上去
+            run_result?;
+
+            let sentinel_output = sentinel_channel
+                .call_method0("getvalue")?
+                .extract::<String>()?;
+
+            serde_json::from_str(sentinel_output.trim()).map_err(|e| {
+                pyo3::exceptions::PyValueError::new_err(format!("哨兵 JSON 解析失败: {}", e))
+            })
         })
     })
     .await;
 
-    match result {
-        Ok(python_result) => python_result.map_err(|e| e.to_string()),
-        Err(e) => Err(format!("System Task Error: {}", e)),
+    let parsed = match result {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => return Err(format!("🐍 Harness Error: {}", e)),
+        Err(e) => return Err(format!("System Task Error: {}", e)),
+    };
+
+    let ok = parsed["ok"].as_bool().unwrap_or(false);
+    let stdout = parsed["stdout"].as_str().unwrap_or_default().to_string();
+    let stderr = parsed["stderr"].as_str().unwrap_or_default().to_string();
+
+    if ok {
+        return Ok(stdout);
     }
+
+    let exc_type = parsed["exc_type"].as_str().unwrap_or("Exception");
+    let exc_msg = parsed["exc_msg"].as_str().unwrap_or("");
+    let lineno = parsed["lineno"]
+        .as_i64()
+        .map(|n| n.to_string())
+        .unwrap_or_else(|| "?".to_string());
+
+    Err(format!(
+        "🐍 {} at line {}: {}\n\n📝 Stdout:\n{}\n\n📝 Stderr:\n{}",
+        exc_type, lineno, exc_msg, stdout, stderr
+    ))
 }
 
 /// Excel 结构
@@ -242,7 +310,68 @@ print(excel_core.peek(r"{}"))
 "#,
         file_path
     );
-    run_python_code(&code).await
+    run_python_snippet(&code).await
+}
+
+/// 读取整张工作表供 `SheetView` 渲染成可滚动/可选区的网格
+///
+/// 跟 [`peek_excel`]/[`get_multi_file_summary`] 不一样，那两个给的是"摘要"（截断
+/// 行数、Markdown 文本），这里要的是完整的表格数据供前端逐格渲染和选区，所以用
+/// `pandas` 读全量数据，`NaN` 统一转成 `None` 再整理成
+/// `{"headers": [...], "rows": [[...], ...]}` 打到 stdout，由 Rust 侧解析成
+/// [`crate::models::SheetGrid`]。
+///
+/// # 多格式支持
+///
+/// `file_path` 先经 [`crate::services::file_format::detect`] 按魔数识别成
+/// [`crate::models::FileFormat`]，再挑对应的 pandas 读取路径（`.xls` 走
+/// `xlrd`，`.ods` 走 `odf`，`.csv` 走 `read_csv` 且不认 `sheet_name`）。四种
+/// 格式解析完都是同一套 `{headers, rows}` 形状，调用方不需要关心原始格式。
+pub async fn read_sheet_grid(
+    file_path: &str,
+    sheet_name: Option<&str>,
+) -> Result<crate::models::SheetGrid, String> {
+    use crate::models::FileFormat;
+
+    let sheet_arg = match sheet_name {
+        Some(name) => format!("r\"{}\"", name),
+        None => "0".to_string(),
+    };
+    let format = crate::services::file_format::detect(file_path);
+    let read_expr = match format {
+        FileFormat::Xlsx => format!(
+            r#"pd.read_excel(r"{}", sheet_name={}, dtype=str, engine="openpyxl")"#,
+            file_path, sheet_arg
+        ),
+        FileFormat::Xls => format!(
+            r#"pd.read_excel(r"{}", sheet_name={}, dtype=str, engine="xlrd")"#,
+            file_path, sheet_arg
+        ),
+        FileFormat::Ods => format!(
+            r#"pd.read_excel(r"{}", sheet_name={}, dtype=str, engine="odf")"#,
+            file_path, sheet_arg
+        ),
+        FileFormat::Csv => format!(r#"pd.read_csv(r"{}", dtype=str)"#, file_path),
+        // 认不出格式时仍按旧行为尝试 pandas 自动探测引擎，让明确的报错（而不是
+        // 我们这边先行拒绝）去驱动 `error_fix_signal`
+        FileFormat::Unknown => format!(
+            r#"pd.read_excel(r"{}", sheet_name={}, dtype=str)"#,
+            file_path, sheet_arg
+        ),
+    };
+    let code = format!(
+        r#"
+import pandas as pd
+import json
+
+df = {}
+df = df.where(pd.notnull(df), None)
+print(json.dumps({{"headers": [str(c) for c in df.columns], "rows": df.values.tolist()}}, ensure_ascii=False, default=str))
+"#,
+        read_expr
+    );
+    let stdout = run_python_snippet(&code).await?;
+    serde_json::from_str(stdout.trim()).map_err(|e| format!("解析工作表网格失败: {}", e))
 }
 
 /// 读取多文件上下文 (Multi-Sheet Context)
@@ -349,36 +478,94 @@ print(final_report)
     result.unwrap_or_else(|_| "系统错误".to_string())
 }
 
+/// 获取喂给 AI 的列上下文（宽表场景下按相关性检索，而不是甩全部列）
+///
+/// 实际的抽样、embedding、缓存与 Top-K 检索逻辑都在 [`crate::services::embeddings`]
+/// 里，这里只是把它包成跟仓库里其它 `python::xxx` 函数一样的入口，方便
+/// `InputArea` 继续以 `task::spawn_blocking(|| python::get_excel_columns(...))`
+/// 的方式调用。
+pub fn get_excel_columns(file_path: &str, user_query: &str) -> String {
+    crate::services::embeddings::relevant_columns_context(file_path, user_query)
+}
+
+/// 单个文件能同时占用的备份并发数
+///
+/// 太高会让一堆 `SaveCopyAs` 同时糊在同一个 COM Excel 实例上，反而更慢/更容易
+/// 报错；4 是个比较保守、留了安全余量的默认值。
+const BACKUP_CONCURRENCY: usize = 4;
+/// 每个备份任务启动之间错开的时间，进一步避免瞬间打满 COM
+const BACKUP_LAUNCH_STAGGER: std::time::Duration = std::time::Duration::from_millis(120);
+
 /// 批量创建热备份
 ///
 /// 使用 `shutil.copy2` 进行物理文件复制。
 /// 这天然支持多 Sheet，因为它复制的是整个 `.xlsx` 文件。
+///
+/// # 并发改造
+///
+/// 以前是一个 `for` 循环里 `.await` 串行跑完所有文件，工作簿一多就很慢。现在
+/// 每个文件的备份都 `tokio::spawn` 成独立任务，用 `Semaphore` 限制同时跑的数量
+/// （默认 [`BACKUP_CONCURRENCY`] 个），并且任务之间错开
+/// [`BACKUP_LAUNCH_STAGGER`] 再启动，避免一瞬间甩一堆 `SaveCopyAs` 调用给同一个
+/// COM Excel 实例。最终用 `join_all` 收集结果，只保留真正落盘成功的备份对。
+///
+/// 每次新建备份之后，会按 `AppConfig::backup_retention` 对该原始文件的快照做
+/// 滚动清理，只保留最近 N 个，避免 `backups/` 无限膨胀。
 pub async fn create_batch_backups(target_paths: Vec<String>) -> Vec<(String, String)> {
-    let mut backups = Vec::new();
     let backup_dir = env::current_dir().unwrap_or_default().join("backups");
     if !backup_dir.exists() {
         let _ = fs::create_dir_all(&backup_dir);
     }
+    let retention = crate::services::config::load_config().backup_retention;
 
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
         .as_millis();
 
+    let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(BACKUP_CONCURRENCY));
+
+    let mut tasks = Vec::new();
     for path in target_paths {
-        let file_name = Path::new(&path)
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy();
-        // 备份名为：原名.时间戳.bak，防止冲突
-        let backup_filename = format!("{}.{}.bak", file_name, timestamp);
-        let backup_path = backup_dir
-            .join(&backup_filename)
-            .to_string_lossy()
-            .to_string();
-
-        let code = format!(
-            r#"
+        let semaphore = semaphore.clone();
+        let backup_dir = backup_dir.clone();
+        tasks.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await.ok()?;
+            backup_one(path, backup_dir, timestamp, retention).await
+        }));
+        // 错开下一个任务的启动时间，而不是一次性全部 spawn 出去
+        tokio::time::sleep(BACKUP_LAUNCH_STAGGER).await;
+    }
+
+    futures_util::future::join_all(tasks)
+        .await
+        .into_iter()
+        .filter_map(|joined| joined.ok().flatten())
+        .collect()
+}
+
+/// 备份单个文件：生成备份路径、跑一次性的 xlwings/shutil 脚本、确认落盘、
+/// 按保留策略清理该文件的旧快照。成功才返回 `Some((原路径, 备份路径))`。
+async fn backup_one(
+    path: String,
+    backup_dir: std::path::PathBuf,
+    timestamp: u128,
+    retention: usize,
+) -> Option<(String, String)> {
+    let file_name = Path::new(&path)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    // 备份名为：原名.时间戳.bak，防止冲突
+    let backup_filename = format!("{}.{}.bak", file_name, timestamp);
+    let backup_path = backup_dir
+        .join(&backup_filename)
+        .to_string_lossy()
+        .to_string();
+
+    let code = format!(
+        r#"
 import xlwings as xw
 import os
 import shutil
@@ -396,7 +583,7 @@ try:
                 if book.fullname.lower() == target.lower():
                     wb = book; break
             if wb: break
-    
+
     if wb:
         wb.api.SaveCopyAs(backup)
     else:
@@ -405,18 +592,30 @@ try:
 except:
     pass
 "#,
-            path, backup_path
-        );
-
-        // 我们这里串行执行备份，虽然稍慢但逻辑简单安全
-        if let Ok(_) = run_python_code(&code).await {
-            // 只要没报错，就认为备份成功（即便可能是物理复制）
-            if Path::new(&backup_path).exists() {
-                backups.push((path, backup_path));
-            }
-        }
+        path, backup_path
+    );
+
+    run_python_snippet(&code).await.ok()?;
+    // 只要没报错，就认为备份成功（即便可能是物理复制）；真正落盘才算数
+    if !Path::new(&backup_path).exists() {
+        return None;
     }
-    backups
+
+    // 这一轮备份成功后，立刻按保留策略清理同名文件的旧快照
+    let mut snapshots: Vec<(u128, std::path::PathBuf)> = fs::read_dir(&backup_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let entry_path = entry.path();
+            let name = entry_path.file_name()?.to_str()?.to_string();
+            let (original, ts) = parse_backup_name(&name)?;
+            (original == file_name).then_some((ts, entry_path))
+        })
+        .collect();
+    prune_snapshots(&mut snapshots, retention);
+
+    Some((path, backup_path))
 }
 
 /// 批量热撤销 (Safe Plan B & Visual Audit)
@@ -429,6 +628,19 @@ except:
 ///     3.  如果目标里没有 -> 新建并恢复 (标记为**绿色**)。
 ///     4.  **关键**: 如果目标里多出了 Sheet (无论是 AI 建的还是用户建的) -> **绝不删除**，但标记为**红色**并提示用户。
 ///     5.  **性能优化**: 开启 `screen_updating = False`，加速多表操作。
+///
+/// # 单元格级 Diff 还原 (Cell-Level Reconciliation Upgrade)
+///
+/// 以前每个 Sheet 是 `clear()` 再整体拷贝备份内容，这会把任何合法的后续编辑一
+/// 起冲掉，只能在 Sheet 标签页这个粒度标色。现在改成逐单元格比较备份/目标的
+/// 已用区域，分三类处理：
+/// * 备份值 != 目标值 -> 写回备份值，**标绿**（已恢复）。
+/// * 目标有值但备份没有（AI/用户新增的内容）-> 保留原值不动，**标红**（待确认）。
+/// * 两边相等 -> 不触碰，避免产生无意义的格式变更。
+///
+/// 每个 Sheet 按这三类计数，拼成一条 Markdown 审计摘要（例如
+/// "27 个单元格已恢复，4 个新增单元格已标记"），和原有的整表新增/删除场景的
+/// 标签页红绿标色共存，把"绝不静默删除用户数据"的不变量下沉到单元格粒度。
 pub async fn run_batch_hot_undo(restore_pairs: Vec<(String, String)>) -> Result<String, String> {
     let pairs_repr = format!("{:?}", restore_pairs);
 
@@ -468,30 +680,83 @@ for target_file, backup_file in pairs:
             wb_backup = app.books.open(backup_file)
             
             restored_list = []
-            
-            # 3. [NEW] 核心循环: 以备份文件为“真理”，强制还原所有旧数据
+            sheet_audit = []
+
+            def to_2d(val):
+                # xlwings 对单格/整行/整列会自动"降维"，这里统一升回 2D 方便按坐标比较
+                if val is None:
+                    return [[None]]
+                if not isinstance(val, list):
+                    return [[val]]
+                if not isinstance(val[0], list):
+                    return [val]
+                return val
+
+            # 3. [MODIFIED] 核心循环: 逐单元格 diff 还原，而不是清空整表再覆盖
             for s_bak in wb_backup.sheets:
                 s_name = s_bak.name
-                
+
                 # 尝试在目标中获取同名 Sheet
                 try:
                     s_tgt = wb_target.sheets[s_name]
                 except:
                     # [NEW] 复活逻辑: 如果目标里没有(被误删)，则新建并放到最后
                     s_tgt = wb_target.sheets.add(name=s_name, after=wb_target.sheets[-1])
-                
-                # 暴力恢复内容: 清空 -> 全量复制
-                s_tgt.clear()
-                s_bak.used_range.copy(s_tgt.range('A1'))
-                
+
+                # 读两边各自的 used_range 再取并集，而不是只按备份的形状裁目标：
+                # 只看备份形状会漏掉目标里超出备份范围新增的行/列(最常见的编辑)，
+                # 这些格子永远进不了下面的 diff 循环，也就永远不会被标红审计。
+                bak_used = s_bak.used_range
+                bak_rows, bak_cols = bak_used.shape
+                tgt_used_range = s_tgt.used_range
+                tgt_rows, tgt_cols = tgt_used_range.shape
+
+                n_rows = max(bak_rows, tgt_rows)
+                n_cols = max(bak_cols, tgt_cols)
+                tgt_used = s_tgt.range((1, 1), (n_rows, n_cols))
+
+                bak_vals = to_2d(bak_used.value)
+                tgt_vals = to_2d(tgt_used.value)
+
+                restored_cells = 0
+                flagged_cells = 0
+
+                for r in range(n_rows):
+                    for c in range(n_cols):
+                        b_val = bak_vals[r][c] if r < len(bak_vals) and c < len(bak_vals[r]) else None
+                        t_val = tgt_vals[r][c] if r < len(tgt_vals) and c < len(tgt_vals[r]) else None
+                        if b_val == t_val:
+                            continue
+
+                        cell = s_tgt.range((r + 1, c + 1))
+                        bak_has_value = b_val is not None and b_val != ""
+                        tgt_has_value = t_val is not None and t_val != ""
+
+                        if bak_has_value:
+                            # (a) 备份与目标不一致 -> 以备份为准写回，标绿代表"已恢复"
+                            cell.value = b_val
+                            try:
+                                cell.color = (198, 239, 206)
+                            except: pass
+                            restored_cells += 1
+                        elif tgt_has_value:
+                            # (b) 备份里没有但目标有 -> AI/用户新增内容，保留原值，标红待确认
+                            try:
+                                cell.color = (255, 199, 206)
+                            except: pass
+                            flagged_cells += 1
+
+                restored_list.append(s_name)
+                sheet_audit.append(
+                    f"- **{{s_name}}**: {{restored_cells}} 个单元格已恢复，{{flagged_cells}} 个新增单元格已标记待确认"
+                )
+
                 # [NEW] 视觉标记: 恢复成功的表标为绿色 (ColorIndex: 4 或 RGB)
-                try: 
+                try:
                     # 绿色，代表 "Safe / Restored"
-                    s_tgt.api.Tab.Color = 5296274 
+                    s_tgt.api.Tab.Color = 5296274
                 except: pass
-                
-                restored_list.append(s_name)
-            
+
             # 4. [NEW] 审计逻辑: 检查多余的 Sheet (Safe Mode)
             # 我们绝不自动删除用户可能新建的表，只做标记
             tgt_sheets = [s.name for s in wb_target.sheets]
@@ -511,7 +776,10 @@ for target_file, backup_file in pairs:
             
             # 5. 构造反馈消息 (使用 Markdown 语法)
             msg = f"✅ 已回溯 **{{os.path.basename(target_file)}}**"
-            
+
+            if sheet_audit:
+                msg += "\n\n" + "\n".join(sheet_audit)
+
             if extra_sheets:
                 # 🔥 这里使用了 > 引用语法，配合 CSS 形成红色警告框
                 msg += f"\n\n> 🚨 **检测到新增工作表（已保留）**"
@@ -535,7 +803,7 @@ print("\n".join(log))
         pairs_repr
     );
 
-    run_python_code(&code).await
+    run_python_snippet(&code).await
 }
 
 /// 物理恢复函数（用于降级）
@@ -543,3 +811,261 @@ pub fn restore_file_physical(original: &str, backup: &str) -> Result<(), std::io
     fs::copy(backup, original)?;
     Ok(())
 }
+
+// ============================================================================
+// 持久化 Python 内核 (Persistent Kernel)
+// ============================================================================
+//
+// AI 回合的代码执行跟 peek/备份/撤销这些一次性脚本不一样：用户经常会接着上
+// 一轮的结果继续提问（"把刚才的结果再按 xxx 过滤一下"），如果每次都重新起一个
+// 解释器，DataFrame 之类的中间状态就全丢了，还得让模型重新读一遍 Excel。
+//
+// 这里按"打开的文件"为粒度，每个文件维护一个常驻的 Python 子进程（灵感上类似
+// Jupyter 的 kernel），在它的全局命名空间里持续 exec 后续代码。Rust 和子进程
+// 之间用最简单的按行 JSON 协议通信：
+//   请求: {"op_id": "...", "code": "..."}
+//   回执: {"op_id": "...", "status": "ok"|"error", "message", "preview", "stdout", "image"}
+// `op_id` 由调用方（`operation_id`）生成，用来把请求和回执对上号。
+
+/// 单个内核子进程的句柄：只有输入输出管道，不含 `Child` 本体
+///
+/// `Child` 单独存在 [`kill_handles`] 里，跟这里的管道分开上锁，见该函数文档。
+struct PyKernel {
+    stdin: ChildStdin,
+    stdout: BufReader<std::process::ChildStdout>,
+    /// 本内核累计执行过多少次代码，纯粹用于日志排查，不参与协议匹配
+    exec_count: u64,
+}
+
+/// 按文件路径索引的内核注册表：同一个文件的多轮对话复用同一个子进程
+///
+/// 每个内核单独包一把 `Mutex`（而不是整张表共用外层那把），`run_python_code`
+/// 拿到 `Arc` 后立刻释放外层表锁，真正阻塞读写时只占着这一个文件自己的锁，
+/// 不会连累其它文件的请求排队。
+fn kernels() -> &'static Mutex<HashMap<String, Arc<Mutex<PyKernel>>>> {
+    static KERNELS: OnceLock<Mutex<HashMap<String, Arc<Mutex<PyKernel>>>>> = OnceLock::new();
+    KERNELS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 按文件路径索引的子进程句柄，专门给 [`cancel_python_execution`]/[`restart_python_kernel`] 杀进程用
+///
+/// 跟 [`kernels`] 分开存放：`run_python_code` 阻塞在 `read_line` 上时，攥着的
+/// 是某个文件自己的内核锁，并不会碰这张表，所以取消操作不需要等一段跑飞的
+/// 代码执行完才能拿到锁——直接在这里找到 `Child` 调 `kill()` 即可。
+fn kill_handles() -> &'static Mutex<HashMap<String, Child>> {
+    static HANDLES: OnceLock<Mutex<HashMap<String, Child>>> = OnceLock::new();
+    HANDLES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 内核子进程里常驻运行的引导脚本
+///
+/// 从 stdin 按行读取 `{op_id, code}`，在同一个全局命名空间里 `exec`，把异常用
+/// `traceback` 捕获成结构化字段，再把回执编码成一行 JSON 写到 stdout。
+const KERNEL_BOOTSTRAP_SRC: &str = r#"
+import sys, json, io, traceback, base64
+
+# matplotlib 是可选依赖：便携式 py_env 里不一定装了它，装了也不能用默认的
+# GUI 后端（常驻子进程没有窗口系统），所以这里强制切到 Agg 纯渲染后端。
+try:
+    import matplotlib
+    matplotlib.use("Agg")
+    import matplotlib.pyplot as plt
+    _HAS_MPL = True
+except Exception:
+    _HAS_MPL = False
+
+namespace = {"__name__": "__main__"}
+
+for raw_line in sys.stdin:
+    raw_line = raw_line.strip()
+    if not raw_line:
+        continue
+    try:
+        req = json.loads(raw_line)
+    except Exception:
+        continue
+
+    op_id = req.get("op_id")
+    code = req.get("code", "")
+
+    stdout_capture = io.StringIO()
+    old_stdout = sys.stdout
+    sys.stdout = stdout_capture
+    status = "ok"
+    message = "执行成功"
+    try:
+        exec(code, namespace)
+    except Exception as e:
+        status = "error"
+        message = "{}\n{}".format(e, traceback.format_exc())
+    finally:
+        sys.stdout = old_stdout
+
+    # 如果这段代码画了图（plt.plot/plt.bar/... 直到 plt.show()/savefig() 之前），
+    # 捕获当前活跃的 figure 存成 base64 PNG，而不是指望 plt.show() 真的弹窗。
+    image_b64 = None
+    if _HAS_MPL and plt.get_fignums():
+        try:
+            buf = io.BytesIO()
+            plt.savefig(buf, format="png", bbox_inches="tight")
+            image_b64 = "data:image/png;base64," + base64.b64encode(buf.getvalue()).decode("ascii")
+        except Exception:
+            image_b64 = None
+        finally:
+            plt.close("all")
+
+    reply = {
+        "op_id": op_id,
+        "status": status,
+        "message": message,
+        "preview": None,
+        "stdout": stdout_capture.getvalue(),
+        "image": image_b64,
+    }
+    print(json.dumps(reply), flush=True)
+"#;
+
+/// 启动一个新的内核子进程
+///
+/// 依赖 `init_python_env` 已经把便携式 `py_env` 加进了 `PATH`，所以这里直接用
+/// `python` 这个名字就能解析到跟 PyO3 嵌入式解释器同一套环境。
+fn spawn_kernel(file_path: &str) -> io::Result<(Child, PyKernel)> {
+    let mut child = Command::new("python")
+        .arg("-u")
+        .arg("-c")
+        .arg(KERNEL_BOOTSTRAP_SRC)
+        .env("EXCEL_AGENT_TARGET_FILE", file_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdin = child.stdin.take().expect("kernel 子进程未配置 stdin 管道");
+    let stdout = BufReader::new(
+        child
+            .stdout
+            .take()
+            .expect("kernel 子进程未配置 stdout 管道"),
+    );
+    Ok((
+        child,
+        PyKernel {
+            stdin,
+            stdout,
+            exec_count: 0,
+        },
+    ))
+}
+
+fn error_result(message: String) -> String {
+    serde_json::to_string(&PyExecResult {
+        status: "error".into(),
+        message,
+        preview: None,
+        stdout: None,
+        image: None,
+    })
+    .unwrap_or_else(|_| r#"{"status":"error","message":"内部序列化失败"}"#.to_string())
+}
+
+/// 在某个文件对应的持久内核里执行一段代码，返回 [`PyExecResult`] 序列化后的 JSON
+///
+/// 同一个 `file_path` 第一次调用时惰性启动内核，之后的调用复用同一个子进程和
+/// 同一套全局命名空间，这样后续回合能直接接着用前面加载好的 DataFrame。
+/// 本函数是阻塞的，调用方（见 `InputArea`）需要用 `tokio::task::spawn_blocking`
+/// 包一层，不要直接在异步上下文里调用。
+pub fn run_python_code(file_path: &str, code: &str, op_id: &str) -> String {
+    let kernel_arc = {
+        let mut registry = kernels().lock().unwrap();
+        match registry.get(file_path) {
+            Some(k) => k.clone(),
+            None => match spawn_kernel(file_path) {
+                Ok((child, kernel)) => {
+                    kill_handles()
+                        .lock()
+                        .unwrap()
+                        .insert(file_path.to_string(), child);
+                    let arc = Arc::new(Mutex::new(kernel));
+                    registry.insert(file_path.to_string(), arc.clone());
+                    arc
+                }
+                Err(e) => return error_result(format!("❌ 无法启动 Python 内核: {}", e)),
+            },
+        }
+    };
+
+    // 外层表锁已经释放，接下来阻塞读写只占着这一个文件自己的内核锁，不挡
+    // 其它文件的请求，也不挡 cancel/restart（它们走的是 kill_handles）。
+    let mut kernel = kernel_arc.lock().unwrap();
+
+    let request = serde_json::json!({ "op_id": op_id, "code": code });
+    let line = match serde_json::to_string(&request) {
+        Ok(s) => s,
+        Err(e) => return error_result(format!("❌ 请求序列化失败: {}", e)),
+    };
+
+    if let Err(e) = kernel
+        .stdin
+        .write_all(line.as_bytes())
+        .and_then(|_| kernel.stdin.write_all(b"\n"))
+        .and_then(|_| kernel.stdin.flush())
+    {
+        drop(kernel);
+        discard_kernel(file_path);
+        return error_result(format!("❌ 内核已断开，请重新发送: {}", e));
+    }
+    kernel.exec_count += 1;
+
+    // 内核是严格按请求顺序处理并回写的，所以读到的第一行就是这次请求的回执；
+    // 万一撞上了陈旧的残留行（理论上不该发生），继续往下读直到 op_id 对上。
+    let mut response_line = String::new();
+    loop {
+        response_line.clear();
+        match kernel.stdout.read_line(&mut response_line) {
+            Ok(0) => {
+                drop(kernel);
+                discard_kernel(file_path);
+                return error_result("❌ 内核进程意外退出".to_string());
+            }
+            Ok(_) => {
+                let trimmed = response_line.trim();
+                match serde_json::from_str::<serde_json::Value>(trimmed) {
+                    Ok(value) if value.get("op_id").and_then(|v| v.as_str()) == Some(op_id) => {
+                        return trimmed.to_string();
+                    }
+                    _ => continue,
+                }
+            }
+            Err(e) => {
+                drop(kernel);
+                discard_kernel(file_path);
+                return error_result(format!("❌ 读取内核输出失败: {}", e));
+            }
+        }
+    }
+}
+
+/// 从两张注册表里一并摘掉某个文件的内核，不负责杀进程（调用方视情况决定）
+fn discard_kernel(file_path: &str) {
+    kernels().lock().unwrap().remove(file_path);
+    kill_handles().lock().unwrap().remove(file_path);
+}
+
+/// 中止某个文件当前可能卡住的执行
+///
+/// 这里没有给内核协议加真正的"中断信号"，卡死的 cell 直接杀掉子进程重启来
+/// 解决，简单粗暴但可靠；代价是该文件的命名空间会被清空，用户需要重新执行
+/// 前置步骤。跟 [`restart_python_kernel`] 是同一套实现。
+///
+/// `Child` 存在单独的 [`kill_handles`] 里，这里只锁那张表，不碰 `run_python_code`
+/// 正攥着的内核锁，所以就算一段代码跑飞卡住了，取消也能立刻生效。
+pub fn cancel_python_execution(file_path: &str) {
+    restart_python_kernel(file_path);
+}
+
+/// 丢弃某个文件的内核并在下次调用时重新启动，用于显式"重启内核"操作
+pub fn restart_python_kernel(file_path: &str) {
+    kernels().lock().unwrap().remove(file_path);
+    if let Some(mut child) = kill_handles().lock().unwrap().remove(file_path) {
+        let _ = child.kill();
+    }
+}
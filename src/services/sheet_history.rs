@@ -0,0 +1,199 @@
+//! 撤销/重做历史 + 全表搜索
+//!
+//! `SheetView` 里每一次编辑（改格子、粘贴区域、插/删行列、合并/取消合并）都会
+//! 生成一条 [`SheetEdit`]，推进这里的撤销栈；`undo`/`redo` 只拿这一条操作的
+//! 最小前后快照去改 `SheetGrid`，不重新克隆整张表，所以花的时间只跟受影响区域
+//! 大小有关，跟表本身多大无关。
+//!
+//! 搜索走的是同一份 `SheetGrid`：子串按大小写不敏感匹配，纯数字查询额外按数值
+//! 相等比较（这样 "1.50" 能命中存成 "1.5" 的单元格），命中位置原样交给调用方
+//! 去驱动 `selected_range` 跳转。
+
+use crate::models::{CellRange, SheetEdit, SheetGrid};
+use std::collections::VecDeque;
+
+/// 撤销栈最多保留的操作数，超出的最旧记录直接丢弃——跟
+/// `AppConfig::backup_retention` 一样，撤销历史不需要无限增长
+const MAX_HISTORY: usize = 200;
+
+/// 撑着 `SheetView` 的撤销/重做栈
+///
+/// 新的编辑一来，`redo_stack` 就整个清空：标准撤销语义，编辑了新内容之后，
+/// 之前被撤销掉的"未来"就不再有意义了。
+#[derive(Debug, Default, Clone)]
+pub struct EditHistory {
+    undo_stack: VecDeque<SheetEdit>,
+    redo_stack: Vec<SheetEdit>,
+}
+
+impl EditHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一条已经应用到 `grid` 上的操作
+    pub fn push(&mut self, edit: SheetEdit) {
+        if self.undo_stack.len() >= MAX_HISTORY {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack.push_back(edit);
+        self.redo_stack.clear();
+    }
+
+    /// 把一条新操作应用到 `grid` 上并记入撤销栈——`SheetView` 里所有会改动表格
+    /// 的交互（编辑格子、粘贴、插/删行列、合并/取消合并）都走这一个入口，保证
+    /// "改了表" 和 "记进历史" 不会有一个漏掉
+    pub fn apply_and_push(&mut self, grid: &mut SheetGrid, edit: SheetEdit) {
+        apply_forward(grid, &edit);
+        self.push(edit);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// 撤销最近一条操作；栈空就什么都不做，返回 `false`
+    pub fn undo(&mut self, grid: &mut SheetGrid) -> bool {
+        let Some(edit) = self.undo_stack.pop_back() else {
+            return false;
+        };
+        apply_inverse(grid, &edit);
+        self.redo_stack.push(edit);
+        true
+    }
+
+    /// 重做上一次被撤销的操作；栈空就什么都不做，返回 `false`
+    pub fn redo(&mut self, grid: &mut SheetGrid) -> bool {
+        let Some(edit) = self.redo_stack.pop() else {
+            return false;
+        };
+        apply_forward(grid, &edit);
+        self.undo_stack.push_back(edit);
+        true
+    }
+}
+
+/// 把一条操作重新应用一遍（`redo` 用）
+fn apply_forward(grid: &mut SheetGrid, edit: &SheetEdit) {
+    match edit {
+        SheetEdit::CellEdit {
+            row, col, after, ..
+        } => set_cell(grid, *row, *col, after.clone()),
+        SheetEdit::RangePaste { range, after, .. } => write_range(grid, range, after),
+        SheetEdit::RowInsert { at } => insert_row(grid, *at),
+        SheetEdit::RowDelete { at, .. } => remove_row(grid, *at),
+        SheetEdit::ColInsert { at, header } => insert_col(grid, *at, header.clone()),
+        SheetEdit::ColDelete { at, .. } => remove_col(grid, *at),
+        SheetEdit::Merge { range } => add_merge(grid, *range),
+        SheetEdit::Unmerge { range } => remove_merge(grid, *range),
+    }
+}
+
+/// 把一条操作的效果撤回去（`undo` 用）
+fn apply_inverse(grid: &mut SheetGrid, edit: &SheetEdit) {
+    match edit {
+        SheetEdit::CellEdit {
+            row, col, before, ..
+        } => set_cell(grid, *row, *col, before.clone()),
+        SheetEdit::RangePaste { range, before, .. } => write_range(grid, range, before),
+        SheetEdit::RowInsert { at } => remove_row(grid, *at),
+        SheetEdit::RowDelete { at, cells } => {
+            insert_row(grid, *at);
+            grid.rows[*at] = cells.clone();
+        }
+        SheetEdit::ColInsert { at, .. } => remove_col(grid, *at),
+        SheetEdit::ColDelete { at, header, cells } => {
+            insert_col(grid, *at, header.clone());
+            for (row, value) in grid.rows.iter_mut().zip(cells.iter()) {
+                row[*at] = value.clone();
+            }
+        }
+        SheetEdit::Merge { range } => remove_merge(grid, *range),
+        SheetEdit::Unmerge { range } => add_merge(grid, *range),
+    }
+}
+
+fn set_cell(grid: &mut SheetGrid, row: usize, col: usize, value: Option<String>) {
+    if let Some(cell) = grid.rows.get_mut(row).and_then(|r| r.get_mut(col)) {
+        *cell = value;
+    }
+}
+
+fn write_range(grid: &mut SheetGrid, range: &CellRange, values: &[Vec<Option<String>>]) {
+    for (r, row_values) in (range.row_start..=range.row_end).zip(values.iter()) {
+        for (c, value) in (range.col_start..=range.col_end).zip(row_values.iter()) {
+            set_cell(grid, r, c, value.clone());
+        }
+    }
+}
+
+fn insert_row(grid: &mut SheetGrid, at: usize) {
+    let width = grid.headers.len();
+    grid.rows.insert(at.min(grid.rows.len()), vec![None; width]);
+}
+
+fn remove_row(grid: &mut SheetGrid, at: usize) {
+    if at < grid.rows.len() {
+        grid.rows.remove(at);
+    }
+}
+
+fn insert_col(grid: &mut SheetGrid, at: usize, header: String) {
+    let at = at.min(grid.headers.len());
+    grid.headers.insert(at, header);
+    for row in grid.rows.iter_mut() {
+        row.insert(at.min(row.len()), None);
+    }
+}
+
+fn remove_col(grid: &mut SheetGrid, at: usize) {
+    if at < grid.headers.len() {
+        grid.headers.remove(at);
+    }
+    for row in grid.rows.iter_mut() {
+        if at < row.len() {
+            row.remove(at);
+        }
+    }
+}
+
+fn add_merge(grid: &mut SheetGrid, range: CellRange) {
+    if !grid.merges.contains(&range) {
+        grid.merges.push(range);
+    }
+}
+
+fn remove_merge(grid: &mut SheetGrid, range: CellRange) {
+    grid.merges.retain(|m| m != &range);
+}
+
+/// 在 `grid` 里搜 `query`，按行优先顺序返回所有命中的 `(row, col)`
+///
+/// 子串匹配大小写不敏感；`query` 能解析成数字时额外按数值相等命中（单元格也得
+/// 能解析成数字），这样 "1.50" 能找到存成 "1.5" 的格子，纯文本搜索找不到的场景。
+pub fn search_sheet(grid: &SheetGrid, query: &str) -> Vec<(usize, usize)> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let query_lower = query.to_lowercase();
+    let query_number = query.parse::<f64>().ok();
+
+    let mut hits = Vec::new();
+    for (row, cells) in grid.rows.iter().enumerate() {
+        for (col, cell) in cells.iter().enumerate() {
+            let Some(text) = cell else { continue };
+            let is_text_match = text.to_lowercase().contains(&query_lower);
+            let is_number_match =
+                query_number.is_some_and(|q| text.parse::<f64>().is_ok_and(|v| v == q));
+            if is_text_match || is_number_match {
+                hits.push((row, col));
+            }
+        }
+    }
+    hits
+}
@@ -0,0 +1,117 @@
+//! 语音服务：语音转文字 (STT) 与文字转语音 (TTS)
+//!
+//! 走 OpenAI 兼容的 `/audio/transcriptions`（Whisper 协议）与 `/audio/speech`
+//! 两个接口，地址/密钥来自 [`ModelProfile::effective_voice_base_url`] /
+//! [`ModelProfile::effective_voice_api_key`]（没单独配置语音接口时回退到聊天
+//! 接口本身，很多网关本来就是同一个 base_url 下挂多种能力）。
+
+use crate::models::ModelProfile;
+use directories::ProjectDirs;
+use reqwest::multipart;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+const APP_QUALIFIER: &str = "com";
+const APP_ORG: &str = "excelagent";
+const APP_NAME: &str = "excel_agent";
+
+fn tts_cache_dir() -> PathBuf {
+    let dir = if let Some(proj_dirs) = ProjectDirs::from(APP_QUALIFIER, APP_ORG, APP_NAME) {
+        proj_dirs.data_local_dir().join("tts_cache")
+    } else {
+        PathBuf::from("tts_cache")
+    };
+    let _ = fs::create_dir_all(&dir);
+    dir
+}
+
+/// 用文本内容的哈希做缓存文件名，同一句话第二次朗读直接命中缓存
+fn cache_path_for(text: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    tts_cache_dir().join(format!("{:x}.mp3", hasher.finish()))
+}
+
+/// 把一段录音发给 STT 接口，返回识别出的文本
+pub async fn transcribe(profile: &ModelProfile, audio_bytes: Vec<u8>) -> Result<String, String> {
+    let client = reqwest::Client::new();
+
+    let part = multipart::Part::bytes(audio_bytes)
+        .file_name("audio.webm")
+        .mime_str("audio/webm")
+        .map_err(|e| e.to_string())?;
+    let form = multipart::Form::new()
+        .text("model", "whisper-1")
+        .part("file", part);
+
+    let response = client
+        .post(format!(
+            "{}/audio/transcriptions",
+            profile.effective_voice_base_url().trim_end_matches('/')
+        ))
+        .header(
+            "Authorization",
+            format!("Bearer {}", profile.effective_voice_api_key()),
+        )
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "STT 接口返回错误: {}",
+            response.text().await.unwrap_or_default()
+        ));
+    }
+
+    let body: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+    body["text"]
+        .as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| "STT 响应里没有 text 字段".to_string())
+}
+
+/// 把文本发给 TTS 接口合成语音，按文本哈希缓存到磁盘，命中缓存时不再请求网络
+///
+/// 返回值是本地音频文件的路径，播放交给调用方（`InputArea` 里通过
+/// `dioxus::document::eval` 起一个 `new Audio(...)` 播放）。
+pub async fn synthesize(profile: &ModelProfile, text: &str) -> Result<PathBuf, String> {
+    let cache_path = cache_path_for(text);
+    if cache_path.exists() {
+        return Ok(cache_path);
+    }
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!(
+            "{}/audio/speech",
+            profile.effective_voice_base_url().trim_end_matches('/')
+        ))
+        .header(
+            "Authorization",
+            format!("Bearer {}", profile.effective_voice_api_key()),
+        )
+        .header("Content-Type", "application/json")
+        .json(&serde_json::json!({
+            "model": "tts-1",
+            "voice": "alloy",
+            "input": text,
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "TTS 接口返回错误: {}",
+            response.text().await.unwrap_or_default()
+        ));
+    }
+
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    fs::write(&cache_path, &bytes).map_err(|e| e.to_string())?;
+    Ok(cache_path)
+}